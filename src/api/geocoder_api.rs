@@ -0,0 +1,237 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::WeatherError;
+
+/// Place an address is resolved to by [`Geocoder`]. Every [`crate::api::provider_api::WeatherProvider`]
+/// is able to serve a report for it, no matter which geocoder resolved it.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Location {
+    /// Canonical name of the place.
+    pub name: String,
+
+    /// Latitude, degrees.
+    pub lat: f32,
+
+    /// Longitude, degrees.
+    pub lon: f32,
+
+    /// Country code or name, depends on geocoder.
+    pub country: Option<String>,
+
+    /// State, region or other first level administrative area.
+    pub state: Option<String>,
+
+    /// IANA time zone, e.g. `Europe/Kyiv`.
+    pub timezone: Option<String>,
+
+    /// Ids the place is known by to particular providers, e.g. Accuweather's location key.
+    /// Lets a provider skip its own lookup when its geocoder resolved the address.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub provider_ids: BTreeMap<String, String>,
+}
+
+impl Location {
+    /// Parses `lat,lon` or `geo:lat,lon` URI (RFC 5870, altitude and parameters are ignored) so geocoding may be skipped.
+    /// `None` if the address is not coordinates, [`WeatherError::InvalidCoordinatesError`] if they are out of range.
+    pub fn from_coordinates(address: &str) -> Result<Option<Location>, WeatherError> {
+        let address = address.trim();
+        let coordinates = match address.get(..4) {
+            Some(scheme) if scheme.eq_ignore_ascii_case("geo:") => {
+                address[4..].split(';').next().unwrap_or_default()
+            }
+            _ => address,
+        };
+        let mut values = coordinates
+            .split(',')
+            .map(|value| value.trim().parse::<f32>());
+        let (lat, lon) = match (values.next(), values.next(), values.next()) {
+            (Some(Ok(lat)), Some(Ok(lon)), None) => (lat, lon),
+            // geo: URI may have altitude as the third one.
+            (Some(Ok(lat)), Some(Ok(lon)), Some(Ok(_))) if coordinates != address => (lat, lon),
+            _ => return Ok(None),
+        };
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+            return Err(WeatherError::InvalidCoordinatesError(address.to_owned()));
+        }
+        Ok(Some(Location {
+            lat,
+            lon,
+            ..Default::default()
+        }))
+    }
+}
+
+/// Coordinates only if there is no name, as for locations parsed by [`Location::from_coordinates`].
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts: Vec<&str> = [Some(&self.name), self.state.as_ref(), self.country.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .filter(|part| !part.is_empty())
+            .collect();
+        if parts.is_empty() {
+            write!(f, "{}, {}", self.lat, self.lon)
+        } else {
+            write!(f, "{} ({}, {})", parts.join(", "), self.lat, self.lon)
+        }
+    }
+}
+
+/// Narrows down locations an ambiguous address is resolved to.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LocationFilter {
+    /// Country code or name, case insensitive.
+    pub country: Option<String>,
+
+    /// Beginning of state name, case insensitive, e.g. `new` for New York.
+    pub state: Option<String>,
+}
+
+impl LocationFilter {
+    /// Whether location satisfies every condition set.
+    pub fn matches(&self, location: &Location) -> bool {
+        let country_matches = self.country.as_ref().is_none_or(|country| {
+            location
+                .country
+                .as_ref()
+                .is_some_and(|c| c.eq_ignore_ascii_case(country))
+        });
+        let state_matches = self.state.as_ref().is_none_or(|state| {
+            location
+                .state
+                .as_ref()
+                .is_some_and(|s| s.to_lowercase().starts_with(&state.to_lowercase()))
+        });
+        country_matches && state_matches
+    }
+
+    /// Keeps matching locations preserving their order.
+    pub fn apply(&self, locations: Vec<Location>) -> Vec<Location> {
+        locations
+            .into_iter()
+            .filter(|location| self.matches(location))
+            .collect()
+    }
+}
+
+/// The common trait for one who wants to resolve addresses to [`Location`]s.
+/// Shared by threads resolving several addresses at once.
+pub trait Geocoder: Debug + Send + Sync {
+    /// Get geocoder's name.
+    fn get_name(&self) -> String;
+
+    /// Resolves the address to all the locations it may refer to, best match first.
+    /// Empty if nothing is found.
+    fn search(&self, address: &str) -> Result<Vec<Location>, WeatherError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Location, LocationFilter};
+    use crate::error::WeatherError;
+
+    fn springfield(state: &str, country: &str) -> Location {
+        Location {
+            name: "Springfield".to_owned(),
+            country: Some(country.to_owned()),
+            state: Some(state.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn locations_filtered_ok() {
+        let locations = vec![
+            springfield("Illinois", "US"),
+            springfield("Missouri", "US"),
+            springfield("Massachusetts", "US"),
+            springfield("Queensland", "AU"),
+        ];
+
+        assert_eq!(
+            locations,
+            LocationFilter::default().apply(locations.clone())
+        );
+
+        let filter = LocationFilter {
+            country: Some("us".to_owned()),
+            state: Some("m".to_owned()),
+        };
+        assert_eq!(
+            vec![
+                springfield("Missouri", "US"),
+                springfield("Massachusetts", "US")
+            ],
+            filter.apply(locations.clone())
+        );
+
+        let filter = LocationFilter {
+            country: Some("UA".to_owned()),
+            state: None,
+        };
+        assert!(filter.apply(locations).is_empty());
+    }
+
+    #[test]
+    fn location_displayed_ok() {
+        let mut location = Location {
+            name: "Springfield".to_owned(),
+            lat: 39.80172,
+            lon: -89.64371,
+            country: Some("US".to_owned()),
+            state: Some("Illinois".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(
+            "Springfield, Illinois, US (39.80172, -89.64371)",
+            location.to_string()
+        );
+
+        location.state = None;
+        assert_eq!(
+            "Springfield, US (39.80172, -89.64371)",
+            location.to_string()
+        );
+    }
+
+    #[test]
+    fn coordinates_parsed_ok() {
+        for address in [
+            "49.8419,24.0315",
+            " 49.8419, 24.0315 ",
+            "geo:49.8419,24.0315",
+            "GEO:49.8419,24.0315,296;u=35",
+        ] {
+            let location = Location::from_coordinates(address).unwrap().unwrap();
+            assert_eq!((49.8419, 24.0315), (location.lat, location.lon));
+            assert_eq!("49.8419, 24.0315", location.to_string());
+        }
+    }
+
+    #[test]
+    fn addresses_are_not_coordinates() {
+        for address in ["Lviv", "Washington, DC", "1,2,3", "49.8419", "geo:Lviv"] {
+            assert_eq!(Ok(None), Location::from_coordinates(address));
+        }
+    }
+
+    #[test]
+    fn out_of_range_coordinates_error_expected() {
+        assert_eq!(
+            Err(WeatherError::InvalidCoordinatesError("91,24".to_owned())),
+            Location::from_coordinates("91,24")
+        );
+        assert_eq!(
+            Err(WeatherError::InvalidCoordinatesError(
+                "geo:49,181".to_owned()
+            )),
+            Location::from_coordinates("geo:49,181")
+        );
+    }
+}
@@ -1 +1,2 @@
-pub mod provider_api;
\ No newline at end of file
+pub mod provider_api;
+pub mod geocoder_api;
\ No newline at end of file
@@ -1,40 +1,311 @@
-use std::fmt::{self, Debug};
+use std::{
+    env,
+    fmt::{self, Debug},
+};
 
-use chrono::NaiveDate;
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
 use dyn_clone::DynClone;
 use serde::{Deserialize, Serialize};
 
-use crate::error::WeatherError;
-
+use crate::{
+    api::geocoder_api::{Geocoder, Location},
+    error::WeatherError,
+    secrets,
+    units::UnitSystem,
+};
 
 /// Struct every implementation of [`WeatherProvider`] should return as a response querying for report.
 /// Titled in the name of Weather Report band.
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+///
+/// Values are expressed in [`WeatherReport::units`]. Providers return metric ones, use [`WeatherReport::convert`] to get others.
+/// Every field is optional since providers fill only what their API returns.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
 pub struct WeatherReport {
-    pub report: String,
+    /// Unit system temperature, pressure, wind speed and precipitation are expressed in.
+    #[serde(default)]
+    pub units: UnitSystem,
+
+    /// Air temperature.
+    pub temperature: Option<f32>,
+
+    /// Apparent ("feels like") temperature.
+    pub feels_like: Option<f32>,
+
+    /// Lowest temperature over the day, if known. Set by forecasts and historical lookups.
+    pub temperature_min: Option<f32>,
+
+    /// Highest temperature over the day, if known. Set by forecasts and historical lookups.
+    pub temperature_max: Option<f32>,
+
+    /// Relative humidity.
+    pub humidity: Option<f32>,
+
+    /// Atmospheric pressure at sea level.
+    pub pressure: Option<f32>,
+
+    /// Wind speed.
+    pub wind_speed: Option<f32>,
+
+    /// Direction the wind blows from, meteorological degrees.
+    pub wind_direction: Option<u16>,
+
+    /// Cloud cover.
+    pub cloud_cover: Option<f32>,
+
+    /// Precipitation amount over the last hour.
+    pub precipitation: Option<f32>,
+
+    /// Provider specific condition code, e.g. OpenWeather's weather id or Accuweather's icon number.
+    pub condition_code: Option<u32>,
+
+    /// Human readable condition description.
+    pub description: Option<String>,
+
+    /// Time the conditions were observed at.
+    pub observed_at: Option<DateTime<Utc>>,
+
+    /// Location the report is for, as resolved by geocoder. Providers leave it empty.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+}
+
+impl WeatherReport {
+    /// Converts all the unit dependent values to the provided unit system.
+    pub fn convert(self, to: UnitSystem) -> Self {
+        let from = self.units;
+        WeatherReport {
+            units: to,
+            temperature: self.temperature.map(|t| from.convert_temperature(t, to)),
+            feels_like: self.feels_like.map(|t| from.convert_temperature(t, to)),
+            temperature_min: self
+                .temperature_min
+                .map(|t| from.convert_temperature(t, to)),
+            temperature_max: self
+                .temperature_max
+                .map(|t| from.convert_temperature(t, to)),
+            pressure: self.pressure.map(|p| from.convert_pressure(p, to)),
+            wind_speed: self.wind_speed.map(|s| from.convert_speed(s, to)),
+            precipitation: self
+                .precipitation
+                .map(|p| from.convert_precipitation(p, to)),
+            ..self
+        }
+    }
 }
 
 impl fmt::Display for WeatherReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let units = self.units;
+        let mut parts = vec![];
+        if let Some(temperature) = self.temperature {
+            parts.push(format!("{temperature} {}", units.temperature_unit()));
+        }
+        if let Some(feels_like) = self.feels_like {
+            parts.push(format!(
+                "feels like {feels_like} {}",
+                units.temperature_unit()
+            ));
+        }
+        if let (Some(min), Some(max)) = (self.temperature_min, self.temperature_max) {
+            parts.push(format!("{min}..{max} {}", units.temperature_unit()));
+        }
+        if let Some(description) = &self.description {
+            parts.push(description.clone());
+        }
+        if let Some(humidity) = self.humidity {
+            parts.push(format!("humidity {humidity}%"));
+        }
+        if let Some(pressure) = self.pressure {
+            parts.push(format!("pressure {pressure} {}", units.pressure_unit()));
+        }
+        if let Some(wind_speed) = self.wind_speed {
+            let speed_unit = units.speed_unit();
+            match self.wind_direction {
+                Some(direction) => {
+                    parts.push(format!("wind {wind_speed} {speed_unit} from {direction}°"))
+                }
+                None => parts.push(format!("wind {wind_speed} {speed_unit}")),
+            }
+        }
+        if let Some(cloud_cover) = self.cloud_cover {
+            parts.push(format!("clouds {cloud_cover}%"));
+        }
+        if let Some(precipitation) = self.precipitation {
+            parts.push(format!(
+                "precipitation {precipitation} {}",
+                units.precipitation_unit()
+            ));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Single entry of [`WeatherForecast`] describing either an hour (or few hours) or a whole day.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ForecastEntry {
+    /// Start of the period the entry describes.
+    pub time: DateTime<Utc>,
+
+    /// Expected conditions. Its `observed_at` is not set, precipitation is expected over the whole period.
+    #[serde(flatten)]
+    pub report: WeatherReport,
+}
+
+impl ForecastEntry {
+    /// Converts all the unit dependent values to the provided unit system.
+    pub fn convert(self, to: UnitSystem) -> Self {
+        ForecastEntry {
+            report: self.report.convert(to),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for ForecastEntry {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.report)
     }
 }
 
+/// Struct every implementation of [`WeatherProvider`] should return as a response querying for forecast.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WeatherForecast {
+    /// One entry per day.
+    pub daily: Vec<ForecastEntry>,
+
+    /// Entries with finer granularity. Empty if provider has no such data.
+    pub hourly: Vec<ForecastEntry>,
+
+    /// Location the forecast is for, as resolved by geocoder. Providers leave it empty.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
 
-/// The common trait for one who wants to implement it's own provider. 
+    /// Shift of the location's time from UTC, entries are shown in the location's time. UTC if unknown.
+    #[serde(skip)]
+    pub offset: Option<FixedOffset>,
+}
+
+impl WeatherForecast {
+    /// Converts all the entries to the provided unit system.
+    pub fn convert(self, to: UnitSystem) -> Self {
+        WeatherForecast {
+            daily: self.daily.into_iter().map(|e| e.convert(to)).collect(),
+            hourly: self.hourly.into_iter().map(|e| e.convert(to)).collect(),
+            ..self
+        }
+    }
+
+    /// Time of the entry in the location's time, see [`WeatherForecast::offset`].
+    pub fn local_time(&self, entry: &ForecastEntry) -> DateTime<FixedOffset> {
+        entry.time.with_timezone(
+            &self
+                .offset
+                .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap()),
+        )
+    }
+
+    /// Takes daily entry for the `date` of the location, `offset` is its shift from UTC.
+    /// The location's date may differ from the one of the machine, so entries aren't counted from today.
+    pub fn into_day(self, offset: FixedOffset, date: NaiveDate) -> Option<ForecastEntry> {
+        self.daily
+            .into_iter()
+            .find(|entry| entry.time.with_timezone(&offset).date_naive() == date)
+    }
+}
+
+/// Environment variable API_KEY of the provider is taken from at runtime, e.g. `WEATHER_OPEN_METEO_API_KEY`.
+pub fn api_key_env_var(provider_name: &str) -> String {
+    let name: String = provider_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("WEATHER_{name}_API_KEY")
+}
+
+/// API_KEY set for the provider in the environment, see [`api_key_env_var`]. Providers prefer it to the
+/// configured one and never save it, so CI secrets stay off the disk.
+pub fn env_api_key(provider_name: &str) -> Option<String> {
+    env::var(api_key_env_var(provider_name))
+        .ok()
+        .filter(|api_key| !api_key.is_empty())
+}
+
+/// API_KEY provider uses given the one of the environment and the saved one, see [`WeatherProvider::get_api_key`].
+pub fn api_key_in_use(env_api_key: Option<String>, saved: Option<String>) -> Option<String> {
+    env_api_key.or_else(|| secrets::reveal(&saved?))
+}
+
+/// Place providers are asked about to check credentials, any would do.
+pub const PROBE_LOCATION: (f32, f32) = (51.5085, -0.1257);
+
+/// What a successful [`WeatherProvider::validate_credentials`] call tells about provider's API.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ApiStatus {
+    /// Requests left for the current period, if the API reports it.
+    pub quota_remaining: Option<u32>,
+}
+
+/// The common trait for one who wants to implement it's own provider.
+/// Providers are asked from several threads at once, see [`crate::concurrency::run_all`].
 #[typetag::serde]
-pub trait WeatherProvider: Debug + DynClone {
+pub trait WeatherProvider: Debug + DynClone + Send + Sync {
     /// Get provider's name.
     fn get_name(&self) -> String;
 
-    /// Get provider's API_KEY.
-    fn get_api_key(&self) -> Option<String>;
+    /// Get provider's API_KEY as it is saved, encrypted unless saved before keys were, see [`secrets::conceal`].
+    fn get_saved_api_key(&self) -> Option<String>;
+
+    /// Get provider's API_KEY, the one of the environment if set, see [`env_api_key`].
+    /// The saved one is decrypted otherwise, `None` if it can't be.
+    fn get_api_key(&self) -> Option<String> {
+        api_key_in_use(env_api_key(&self.get_name()), self.get_saved_api_key())
+    }
+
+    /// Whether provider can't serve reports without API_KEY.
+    fn requires_api_key(&self) -> bool {
+        true
+    }
+
+    /// Set provider's API_KEY, it is saved encrypted. Fails while keys are locked, see [`secrets::conceal`].
+    fn set_api_key(&mut self, api_key: &str) -> Result<(), WeatherError>;
+
+    /// Get base URL provider's requests are sent to.
+    fn get_base_url(&self) -> String;
+
+    /// Set base URL provider's requests are sent to, e.g. a proxy, an enterprise endpoint or a local mock server.
+    /// `None` restores the default one.
+    fn set_base_url(&mut self, base_url: Option<&str>);
+
+    /// Checks API_KEY with one cheap authenticated request about [`PROBE_LOCATION`].
+    /// [`WeatherError::NoApiKeyError`] if it is required but not set.
+    fn validate_credentials(&self) -> Result<ApiStatus, WeatherError>;
+
+    /// Get geocoder backed by provider's own location search. It may be paired with any other provider.
+    fn get_geocoder(&self) -> Box<dyn Geocoder>;
+
+    /// Looks up ids provider knows the location by and adds them to [`Location::provider_ids`],
+    /// so saved locations need no lookup later. Nothing to do for providers querying by coordinates.
+    fn resolve_location_ids(&self, _location: &mut Location) -> Result<(), WeatherError> {
+        Ok(())
+    }
 
-    /// Set provider's API_KEY.
-    fn set_api_key(&mut self, api_key: &str);
+    /// Gets a report for the date. Today's one is current conditions, past dates are looked up in
+    /// provider's history and future ones in its forecast.
+    /// [`WeatherError::DateNotSupportedError`] is returned if provider can't serve the date.
+    fn get_report(
+        &self,
+        location: &Location,
+        date: NaiveDate,
+    ) -> Result<WeatherReport, WeatherError>;
 
-    /// Gets a report. HISTORICAL LOOKUP is not yet implemented.
-    fn get_report(&self, address: &str, date: NaiveDate) -> Result<WeatherReport, WeatherError>;
+    /// Gets a forecast for the next `days` days starting today.
+    fn get_forecast(&self, location: &Location, days: u8) -> Result<WeatherForecast, WeatherError>;
 }
 
 dyn_clone::clone_trait_object!(WeatherProvider);
@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{geocoder_api::Location, provider_api::WeatherReport},
+    consensus::{ConsensusReport, FieldConsensus},
+    units::UnitSystem,
+};
+
+/// Row of the comparison table: report field name and its formatted value.
+struct Row {
+    name: &'static str,
+    cell: fn(&WeatherReport) -> Option<String>,
+}
+
+/// Fields in the order reports are displayed.
+const ROWS: [Row; 13] = [
+    Row {
+        name: "temperature",
+        cell: |r| {
+            r.temperature
+                .map(|t| format!("{t} {}", r.units.temperature_unit()))
+        },
+    },
+    Row {
+        name: "feels_like",
+        cell: |r| {
+            r.feels_like
+                .map(|t| format!("{t} {}", r.units.temperature_unit()))
+        },
+    },
+    Row {
+        name: "temperature_min",
+        cell: |r| {
+            r.temperature_min
+                .map(|t| format!("{t} {}", r.units.temperature_unit()))
+        },
+    },
+    Row {
+        name: "temperature_max",
+        cell: |r| {
+            r.temperature_max
+                .map(|t| format!("{t} {}", r.units.temperature_unit()))
+        },
+    },
+    Row {
+        name: "description",
+        cell: |r| r.description.clone(),
+    },
+    Row {
+        name: "humidity",
+        cell: |r| r.humidity.map(|h| format!("{h}%")),
+    },
+    Row {
+        name: "pressure",
+        cell: |r| {
+            r.pressure
+                .map(|p| format!("{p} {}", r.units.pressure_unit()))
+        },
+    },
+    Row {
+        name: "wind_speed",
+        cell: |r| {
+            r.wind_speed
+                .map(|s| format!("{s} {}", r.units.speed_unit()))
+        },
+    },
+    Row {
+        name: "wind_direction",
+        cell: |r| r.wind_direction.map(|d| format!("{d}°")),
+    },
+    Row {
+        name: "cloud_cover",
+        cell: |r| r.cloud_cover.map(|c| format!("{c}%")),
+    },
+    Row {
+        name: "precipitation",
+        cell: |r| {
+            r.precipitation
+                .map(|p| format!("{p} {}", r.units.precipitation_unit()))
+        },
+    },
+    Row {
+        name: "condition_code",
+        cell: |r| r.condition_code.map(|c| c.to_string()),
+    },
+    Row {
+        name: "observed_at",
+        cell: |r| r.observed_at.map(|t| t.format("%H:%M UTC").to_string()),
+    },
+];
+
+/// Marks cells standing out of the others when stdout is not a terminal.
+const OUTLIER_MARK: &str = "*";
+
+/// Report of a single provider within [`Comparison`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProviderReport {
+    pub provider: String,
+
+    #[serde(flatten)]
+    pub report: WeatherReport,
+}
+
+/// Reports of several providers side by side along with their [`ConsensusReport`],
+/// which tells the cells standing out.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Comparison {
+    /// Unit system values are expressed in.
+    pub units: UnitSystem,
+
+    /// Report of every provider that answered, in the order they were asked.
+    pub reports: Vec<ProviderReport>,
+
+    /// Median, mean and spread of every numeric field.
+    pub fields: Vec<FieldConsensus>,
+
+    /// Location the reports are for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+}
+
+impl Comparison {
+    pub fn new(reports: &[(String, WeatherReport)], units: UnitSystem) -> Self {
+        let consensus = ConsensusReport::new(reports, units);
+        Comparison {
+            units,
+            reports: reports
+                .iter()
+                .map(|(provider, report)| ProviderReport {
+                    provider: provider.clone(),
+                    report: report.clone().convert(units),
+                })
+                .collect(),
+            fields: consensus.fields,
+            location: None,
+        }
+    }
+
+    /// Whether the provider's value of the field stands out of the others.
+    fn is_outlier(&self, field: &str, provider: &str) -> bool {
+        self.fields
+            .iter()
+            .find(|consensus| consensus.field == field)
+            .is_some_and(|consensus| consensus.outliers.iter().any(|p| p == provider))
+    }
+
+    /// Aligned table, one column per provider and one row per field any of them reported.
+    /// Outlying cells are colored if `colored`, marked with `*` otherwise.
+    pub fn to_table(&self, colored: bool) -> String {
+        let mut table: Vec<Vec<(String, bool)>> = vec![];
+        let mut header = vec![(String::new(), false)];
+        header.extend(
+            self.reports
+                .iter()
+                .map(|report| (report.provider.clone(), false)),
+        );
+        table.push(header);
+        for row in &ROWS {
+            let cells: Vec<Option<String>> = self
+                .reports
+                .iter()
+                .map(|report| (row.cell)(&report.report))
+                .collect();
+            if cells.iter().all(Option::is_none) {
+                continue;
+            }
+            let mut line = vec![(row.name.to_owned(), false)];
+            for (report, cell) in self.reports.iter().zip(cells) {
+                let outlier = self.is_outlier(row.name, &report.provider);
+                let mut cell = cell.unwrap_or_else(|| "-".to_owned());
+                if outlier && !colored {
+                    cell.push_str(OUTLIER_MARK);
+                }
+                line.push((cell, outlier));
+            }
+            table.push(line);
+        }
+
+        let widths: Vec<usize> = (0..table[0].len())
+            .map(|column| {
+                table
+                    .iter()
+                    .map(|line| line[column].0.chars().count())
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        table
+            .iter()
+            .map(|line| {
+                let cells: Vec<String> = line
+                    .iter()
+                    .zip(&widths)
+                    .map(|((cell, outlier), width)| {
+                        // Padded before coloring, escape codes take no room on screen.
+                        let padded = format!("{cell:<width$}");
+                        if *outlier && colored {
+                            format!("\x1b[1;31m{padded}\x1b[0m")
+                        } else {
+                            padded
+                        }
+                    })
+                    .collect();
+                cells.join("  ").trim_end().to_owned()
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Comparison;
+    use crate::{api::provider_api::WeatherReport, units::UnitSystem};
+
+    fn reports() -> Vec<(String, WeatherReport)> {
+        let report = |temperature: f32, humidity: f32, description: Option<&str>| WeatherReport {
+            temperature: Some(temperature),
+            humidity: Some(humidity),
+            description: description.map(str::to_owned),
+            ..Default::default()
+        };
+        vec![
+            (
+                "OpenWeather".to_owned(),
+                report(15.88, 82.0, Some("light rain")),
+            ),
+            (
+                "Accuweather".to_owned(),
+                report(16.1, 57.0, Some("Showers")),
+            ),
+            ("Open-Meteo".to_owned(), report(15.2, 80.0, None)),
+        ]
+    }
+
+    #[test]
+    fn table_aligned_ok() {
+        let comparison = Comparison::new(&reports(), UnitSystem::Metric);
+        assert_eq!(
+            "             OpenWeather  Accuweather  Open-Meteo\n\
+             temperature  15.88 C      16.1 C       15.2 C\n\
+             description  light rain   Showers      -\n\
+             humidity     82%          57%*         80%",
+            comparison.to_table(false)
+        );
+    }
+
+    #[test]
+    fn outliers_colored_ok() {
+        let comparison = Comparison::new(&reports(), UnitSystem::Metric);
+        let table = comparison.to_table(true);
+        assert!(table.contains("\x1b[1;31m57%        \x1b[0m"));
+        assert!(!table.contains('*'));
+    }
+
+    #[test]
+    fn converted_to_units_ok() {
+        let comparison = Comparison::new(&reports(), UnitSystem::Imperial);
+        assert_eq!(Some(60.58), comparison.reports[0].report.temperature);
+        assert!(comparison.to_table(false).contains("60.58 F"));
+    }
+}
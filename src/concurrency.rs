@@ -0,0 +1,94 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+/// Number of requests sent at once unless configured otherwise.
+pub const DEFAULT_JOBS: usize = 4;
+
+/// Runs the task for every item on at most `jobs` threads at once.
+/// Results keep the order of items whichever finishes first.
+pub fn run_all<T, R, F>(items: &[T], jobs: usize, task: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let workers = jobs.clamp(1, items.len().max(1));
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = vec![];
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        match items.get(index) {
+                            Some(item) => done.push((index, task(item))),
+                            None => return done,
+                        }
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("Worker thread panicked"))
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::run_all;
+
+    #[test]
+    fn results_keep_order() {
+        let items: Vec<u64> = (0..20).collect();
+        // Later items finish first.
+        let results = run_all(&items, 8, |item| {
+            thread::sleep(Duration::from_millis(20 - item));
+            item * 2
+        });
+        assert_eq!(
+            items.iter().map(|item| item * 2).collect::<Vec<_>>(),
+            results
+        );
+    }
+
+    #[test]
+    fn jobs_limit_respected() {
+        let running = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+        let items = vec![(); 12];
+        run_all(&items, 3, |_| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            most.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(10));
+            running.fetch_sub(1, Ordering::SeqCst);
+        });
+        assert_eq!(3, most.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn requests_overlap() {
+        let items = vec![(); 8];
+        let started = Instant::now();
+        run_all(&items, 8, |_| thread::sleep(Duration::from_millis(100)));
+        assert!(started.elapsed() < Duration::from_millis(400));
+    }
+
+    #[test]
+    fn nothing_to_run_ok() {
+        let items: Vec<u8> = vec![];
+        assert!(run_all(&items, 0, |item| *item).is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{geocoder_api::Location, provider_api::WeatherReport},
+    units::UnitSystem,
+};
+
+/// Report field providers are compared by.
+struct Field {
+    name: &'static str,
+    value: fn(&WeatherReport) -> Option<f32>,
+    unit: fn(UnitSystem) -> &'static str,
+    /// Spread between providers flagged as disagreement, in metric units.
+    disagreement: f32,
+}
+
+const PERCENT: fn(UnitSystem) -> &'static str = |_| "%";
+
+/// Wind direction, condition and description can't be averaged, they are left out.
+const FIELDS: [Field; 9] = [
+    Field {
+        name: "temperature",
+        value: |report| report.temperature,
+        unit: |units| units.temperature_unit(),
+        disagreement: 3.0,
+    },
+    Field {
+        name: "feels_like",
+        value: |report| report.feels_like,
+        unit: |units| units.temperature_unit(),
+        disagreement: 3.0,
+    },
+    Field {
+        name: "temperature_min",
+        value: |report| report.temperature_min,
+        unit: |units| units.temperature_unit(),
+        disagreement: 3.0,
+    },
+    Field {
+        name: "temperature_max",
+        value: |report| report.temperature_max,
+        unit: |units| units.temperature_unit(),
+        disagreement: 3.0,
+    },
+    Field {
+        name: "humidity",
+        value: |report| report.humidity,
+        unit: PERCENT,
+        disagreement: 20.0,
+    },
+    Field {
+        name: "pressure",
+        value: |report| report.pressure,
+        unit: |units| units.pressure_unit(),
+        disagreement: 5.0,
+    },
+    Field {
+        name: "wind_speed",
+        value: |report| report.wind_speed,
+        unit: |units| units.speed_unit(),
+        disagreement: 3.0,
+    },
+    Field {
+        name: "cloud_cover",
+        value: |report| report.cloud_cover,
+        unit: PERCENT,
+        disagreement: 40.0,
+    },
+    Field {
+        name: "precipitation",
+        value: |report| report.precipitation,
+        unit: |units| units.precipitation_unit(),
+        disagreement: 2.0,
+    },
+];
+
+/// What providers report for a single field.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FieldConsensus {
+    /// Name of [`WeatherReport`] field.
+    pub field: String,
+
+    pub median: f32,
+
+    pub mean: f32,
+
+    /// Difference between the highest and the lowest values.
+    pub spread: f32,
+
+    /// Whether the spread is too large to trust the median.
+    pub disagreement: bool,
+
+    /// Value of every provider reporting the field.
+    pub values: BTreeMap<String, f32>,
+
+    /// Providers whose values stand out when they disagree: off the median by more than half of the tolerated spread.
+    pub outliers: Vec<String>,
+}
+
+/// Reports of several providers for the same location and date merged field by field.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConsensusReport {
+    /// Unit system values are expressed in.
+    pub units: UnitSystem,
+
+    /// Providers that answered.
+    pub providers: Vec<String>,
+
+    /// Fields reported by at least one provider.
+    pub fields: Vec<FieldConsensus>,
+
+    /// Location the reports are for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+}
+
+impl ConsensusReport {
+    /// Merges reports of the named providers. Disagreement thresholds are metric ones
+    /// so they hold whatever unit system values are shown in.
+    pub fn new(reports: &[(String, WeatherReport)], units: UnitSystem) -> Self {
+        let fields = FIELDS
+            .iter()
+            .filter_map(|field| {
+                let metric: BTreeMap<&str, f32> = reports
+                    .iter()
+                    .filter_map(|(name, report)| {
+                        (field.value)(&report.clone().convert(UnitSystem::Metric))
+                            .map(|v| (name.as_str(), v))
+                    })
+                    .collect();
+                let values: BTreeMap<String, f32> = reports
+                    .iter()
+                    .filter_map(|(name, report)| {
+                        (field.value)(&report.clone().convert(units)).map(|v| (name.clone(), v))
+                    })
+                    .collect();
+                if values.is_empty() {
+                    return None;
+                }
+                let ordered = sorted(values.values().copied().collect());
+                let metric_ordered = sorted(metric.values().copied().collect());
+                let disagreement = spread(&metric_ordered) > field.disagreement;
+                let metric_median = median(&metric_ordered);
+                let outliers = metric
+                    .iter()
+                    .filter(|(_, value)| {
+                        disagreement && (*value - metric_median).abs() > field.disagreement / 2.0
+                    })
+                    .map(|(name, _)| name.to_string())
+                    .collect();
+                Some(FieldConsensus {
+                    field: field.name.to_owned(),
+                    median: median(&ordered),
+                    mean: round(ordered.iter().sum::<f32>() / ordered.len() as f32),
+                    spread: spread(&ordered),
+                    disagreement,
+                    values,
+                    outliers,
+                })
+            })
+            .collect();
+        ConsensusReport {
+            units,
+            providers: reports.iter().map(|(name, _)| name.clone()).collect(),
+            fields,
+            location: None,
+        }
+    }
+
+    /// Whether providers disagree on any field.
+    pub fn has_disagreement(&self) -> bool {
+        self.fields.iter().any(|field| field.disagreement)
+    }
+}
+
+/// One line per field: median, mean, spread and values of each provider.
+impl fmt::Display for ConsensusReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lines: Vec<String> = self
+            .fields
+            .iter()
+            .map(|consensus| {
+                let unit = FIELDS
+                    .iter()
+                    .find(|field| field.name == consensus.field)
+                    .map_or("", |field| (field.unit)(self.units));
+                let separator = if unit == "%" { "" } else { " " };
+                let values: Vec<String> = consensus
+                    .values
+                    .iter()
+                    .map(|(name, value)| format!("{name} {value}"))
+                    .collect();
+                let mut line = format!(
+                    "{}: {}{separator}{unit}, mean {}, spread {} ({})",
+                    consensus.field,
+                    consensus.median,
+                    consensus.mean,
+                    consensus.spread,
+                    values.join(", ")
+                );
+                if consensus.disagreement {
+                    line.push_str(" - providers disagree");
+                }
+                line
+            })
+            .collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+fn sorted(mut values: Vec<f32>) -> Vec<f32> {
+    values.sort_by(f32::total_cmp);
+    values
+}
+
+/// Median of sorted non empty values.
+fn median(sorted: &[f32]) -> f32 {
+    let middle = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        round((sorted[middle - 1] + sorted[middle]) / 2.0)
+    } else {
+        sorted[middle]
+    }
+}
+
+/// Spread of sorted values, zero if there are none.
+fn spread(sorted: &[f32]) -> f32 {
+    match (sorted.first(), sorted.last()) {
+        (Some(min), Some(max)) => round(max - min),
+        _ => 0.0,
+    }
+}
+
+/// Keeps two decimals to hide floating point noise.
+fn round(value: f32) -> f32 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConsensusReport;
+    use crate::{api::provider_api::WeatherReport, units::UnitSystem};
+
+    fn report(temperature: f32, humidity: Option<f32>) -> WeatherReport {
+        WeatherReport {
+            temperature: Some(temperature),
+            humidity,
+            ..Default::default()
+        }
+    }
+
+    fn reports() -> Vec<(String, WeatherReport)> {
+        vec![
+            ("OpenWeather".to_owned(), report(15.0, Some(80.0))),
+            ("Accuweather".to_owned(), report(16.0, Some(40.0))),
+            ("Open-Meteo".to_owned(), report(15.5, None)),
+        ]
+    }
+
+    #[test]
+    fn fields_merged_ok() {
+        let consensus = ConsensusReport::new(&reports(), UnitSystem::Metric);
+        assert_eq!(3, consensus.providers.len());
+        assert_eq!(2, consensus.fields.len());
+
+        let temperature = &consensus.fields[0];
+        assert_eq!("temperature", temperature.field);
+        assert_eq!(
+            (15.5, 15.5, 1.0),
+            (temperature.median, temperature.mean, temperature.spread)
+        );
+        assert!(!temperature.disagreement);
+        assert!(temperature.outliers.is_empty());
+
+        let humidity = &consensus.fields[1];
+        assert_eq!(
+            (60.0, 60.0, 40.0),
+            (humidity.median, humidity.mean, humidity.spread)
+        );
+        assert_eq!(2, humidity.values.len());
+        assert!(humidity.disagreement);
+        assert_eq!(vec!["Accuweather", "OpenWeather"], humidity.outliers);
+        assert!(consensus.has_disagreement());
+    }
+
+    #[test]
+    fn outliers_found_ok() {
+        let reports = vec![
+            ("OpenWeather".to_owned(), report(15.0, None)),
+            ("Accuweather".to_owned(), report(16.0, None)),
+            ("Open-Meteo".to_owned(), report(22.0, None)),
+        ];
+        let consensus = ConsensusReport::new(&reports, UnitSystem::Metric);
+        assert_eq!(vec!["Open-Meteo"], consensus.fields[0].outliers);
+    }
+
+    #[test]
+    fn disagreement_independent_of_units() {
+        // 2.5 C apart is 4.5 F apart, still within the threshold.
+        let reports = vec![
+            ("OpenWeather".to_owned(), report(15.0, None)),
+            ("Accuweather".to_owned(), report(17.5, None)),
+        ];
+        let consensus = ConsensusReport::new(&reports, UnitSystem::Imperial);
+        let temperature = &consensus.fields[0];
+        assert_eq!((61.25, 4.5), (temperature.median, temperature.spread));
+        assert!(!consensus.has_disagreement());
+    }
+
+    #[test]
+    fn consensus_displayed_ok() {
+        let consensus = ConsensusReport::new(&reports(), UnitSystem::Metric);
+        assert_eq!(
+            "temperature: 15.5 C, mean 15.5, spread 1 (Accuweather 16, Open-Meteo 15.5, OpenWeather 15)\n\
+             humidity: 60%, mean 60, spread 40 (Accuweather 40, OpenWeather 80) - providers disagree",
+            consensus.to_string()
+        );
+    }
+}
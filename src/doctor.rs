@@ -0,0 +1,128 @@
+use std::{fmt, time::Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::provider_api::WeatherProvider,
+    error::{rejection_reason, WeatherError},
+};
+
+/// Outcome of checking a single provider by `doctor` command.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HealthCheck {
+    pub provider: String,
+
+    /// Whether API_KEY is set.
+    pub api_key: bool,
+
+    /// Whether provider answered the request successfully.
+    pub passed: bool,
+
+    /// Round trip of the request, milliseconds. Not set if no request was sent.
+    pub round_trip_ms: Option<u128>,
+
+    /// Requests left for the current period, if the API reports it.
+    pub quota_remaining: Option<u32>,
+
+    /// Reason the check failed.
+    pub error: Option<String>,
+}
+
+impl HealthCheck {
+    /// Validates provider's credentials timing the request. No request is sent if required API_KEY is missing.
+    pub fn run(provider: &dyn WeatherProvider) -> Self {
+        let api_key = provider.get_api_key().is_some();
+        let mut check = HealthCheck {
+            provider: provider.get_name(),
+            api_key,
+            passed: false,
+            round_trip_ms: None,
+            quota_remaining: None,
+            error: None,
+        };
+        if !api_key && provider.requires_api_key() {
+            check.error = Some(WeatherError::NoApiKeyError.to_string());
+            return check;
+        }
+        let started = Instant::now();
+        let result = provider.validate_credentials();
+        check.round_trip_ms = Some(started.elapsed().as_millis());
+        match result {
+            Ok(status) => {
+                check.passed = true;
+                check.quota_remaining = status.quota_remaining;
+            }
+            Err(error) => check.error = Some(rejection_reason(&error)),
+        }
+        check
+    }
+}
+
+/// `Provider: ok, 120 ms, 48 requests left` or `Provider: FAILED, reason`.
+impl fmt::Display for HealthCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = vec![if self.passed { "ok" } else { "FAILED" }.to_owned()];
+        if let Some(round_trip) = self.round_trip_ms {
+            parts.push(format!("{round_trip} ms"));
+        }
+        if let Some(quota) = self.quota_remaining {
+            parts.push(format!("{quota} requests left"));
+        }
+        if let Some(error) = &self.error {
+            parts.push(error.clone());
+        }
+        write!(f, "{}: {}", self.provider, parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HealthCheck;
+    use crate::{
+        api::provider_api::WeatherProvider,
+        mock_server::MockServer,
+        providers::{accuweather_api::AccuweatherProvider, openweather_api::OpenWeatherProvider},
+    };
+
+    #[test]
+    fn missing_api_key_fails_without_request() {
+        let check = HealthCheck::run(&AccuweatherProvider::new("Accuweather", None));
+        assert!(!check.passed);
+        assert!(!check.api_key);
+        assert_eq!(None, check.round_trip_ms);
+        assert_eq!(
+            "Accuweather: FAILED, No API_KEY provided",
+            check.to_string()
+        );
+    }
+
+    #[test]
+    fn check_displayed_ok() {
+        let check = HealthCheck {
+            provider: "Accuweather".to_owned(),
+            api_key: true,
+            passed: true,
+            round_trip_ms: Some(120),
+            quota_remaining: Some(48),
+            error: None,
+        };
+        assert_eq!(
+            "Accuweather: ok, 120 ms, 48 requests left",
+            check.to_string()
+        );
+    }
+
+    #[test]
+    fn provider_message_shown_on_rejection() {
+        let server = MockServer::start(&[(
+            "/data/2.5/weather",
+            401,
+            r#"{"cod":401,"message":"Invalid API key."}"#,
+        )]);
+        let mut provider = OpenWeatherProvider::new("OpenWeather", Some("wrongkey"));
+        provider.set_base_url(Some(&server.url()));
+        let check = HealthCheck::run(&provider);
+        assert!(!check.passed);
+        assert_eq!(Some("Invalid API key.".to_owned()), check.error);
+    }
+}
@@ -1,10 +1,12 @@
 use std::{fmt::Debug, io};
 
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// Represents internal errors.
 #[derive(Error, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
 pub enum WeatherError {
     /// Parsing CLI commands failed.
     #[error("Failed to parse cli.")]
@@ -18,6 +20,18 @@ pub enum WeatherError {
     #[error("Failed to read config file.")]
     ReadConfigFileError,
 
+    /// Configuration file can't be parsed, e.g. it was edited by hand.
+    #[error("Configuration file is corrupt")]
+    CorruptConfigFileError(String),
+
+    /// Configuration file is of a version this build can't read.
+    #[error("Configuration file version {0} is not supported, it is written by a newer version of the app.")]
+    ConfigVersionError(String),
+
+    /// SQLite storage failed to read or write the database.
+    #[error("Failed to access the database")]
+    DatabaseError(String),
+
     /// No default provider set.
     #[error("No provider is found in configuration.")]
     NoSuchProviderError,
@@ -41,6 +55,76 @@ pub enum WeatherError {
     /// No report provided error. Provider specific.
     #[error("No report found for provided location.")]
     NoReportFoundError,
+
+    /// Provider has neither history nor forecast for the date.
+    #[error("Provider can not serve a report for {0}.")]
+    DateNotSupportedError(NaiveDate),
+
+    /// Forecast requested for more days than provider supports.
+    #[error("Forecast is available for 1 to {0} days.")]
+    ForecastDaysError(u8),
+
+    /// No location saved under the name.
+    #[error("No location named {0} is saved.")]
+    NoSuchLocationError(String),
+
+    /// Address looks like coordinates but they are out of range.
+    #[error("Coordinates {0} are out of range, latitude is -90..90 and longitude is -180..180.")]
+    InvalidCoordinatesError(String),
+
+    /// Cities file of offline geocoder can not be read.
+    #[error("Failed to load cities file")]
+    CitiesFileError(String),
+
+    /// Environment variable API_KEY is to be taken from is not set.
+    #[error("Environment variable {0} is not set.")]
+    MissingEnvVarError(String),
+
+    /// Provider did not accept API_KEY being configured.
+    #[error("API_KEY is rejected")]
+    ApiKeyRejectedError(String),
+
+    /// Some providers failed the check of `doctor` command.
+    #[error("{0} of {1} providers failed the check.")]
+    HealthCheckError(usize, usize),
+
+    /// Key file API keys are encrypted with can not be read or created.
+    #[error("Failed to read the key file")]
+    KeyFileError(String),
+
+    /// Passphrase API keys are encrypted with is mistyped.
+    #[error("Passphrase is wrong.")]
+    WrongPassphraseError,
+
+    /// API_KEY is saved before keys are unlocked, it would be saved in plain text.
+    #[error("API keys are locked, the key can not be saved encrypted.")]
+    ApiKeysLockedError,
+
+    /// Full API_KEY is asked for while keys are not protected with a passphrase.
+    #[error("API keys are shown in full only when they are protected with a passphrase. Set one with `weather key passphrase`.")]
+    PassphraseRequiredError,
+
+    /// Location picked by number which is not among the matching ones.
+    #[error("Can not pick location {0}, there are {1} matching the address.")]
+    LocationPickError(usize, usize),
+}
+
+/// Human readable reason of failed request: message of JSON error body if there is one, e.g.
+/// `{"cod":401,"message":"Invalid API key."}`, the error itself otherwise.
+pub fn rejection_reason(error: &WeatherError) -> String {
+    if let WeatherError::HttpError(body) = error {
+        let message = serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .and_then(|value| {
+                ["message", "Message", "reason"]
+                    .iter()
+                    .find_map(|field| value.get(field)?.as_str().map(str::to_owned))
+            });
+        if let Some(message) = message {
+            return message;
+        }
+    }
+    format!("{error:?}")
 }
 
 // Since main returns a Result and Err is forced to impl Debug need to override Debug to show human redable errors
@@ -54,10 +138,30 @@ impl Debug for WeatherError {
             | Self::NoDefaultProviderError
             | Self::NoApiKeyError
             | Self::NoLocationFoundError
-            | Self::NoReportFoundError => {
+            | Self::NoReportFoundError
+            | Self::WrongPassphraseError
+            | Self::PassphraseRequiredError
+            | Self::ApiKeysLockedError => {
+                write!(f, "{}", self)
+            }
+            Self::DateNotSupportedError(_)
+            | Self::ForecastDaysError(_)
+            | Self::LocationPickError(_, _)
+            | Self::HealthCheckError(_, _) => write!(f, "{}", self),
+            Self::HttpError(message)
+            | Self::CitiesFileError(message)
+            | Self::ApiKeyRejectedError(message)
+            | Self::KeyFileError(message)
+            | Self::CorruptConfigFileError(message)
+            | Self::DatabaseError(message) => {
+                write!(f, "{}. {}", self, message)
+            }
+            Self::InvalidCoordinatesError(_)
+            | Self::NoSuchLocationError(_)
+            | Self::MissingEnvVarError(_)
+            | Self::ConfigVersionError(_) => {
                 write!(f, "{}", self)
             }
-            Self::HttpError(message) => write!(f, "{}. {}", self, message),
         }
     }
 }
@@ -80,8 +184,43 @@ impl From<serde_json::Error> for WeatherError {
     }
 }
 
+impl From<rusqlite::Error> for WeatherError {
+    fn from(error: rusqlite::Error) -> Self {
+        Self::DatabaseError(error.to_string())
+    }
+}
+
 impl From<reqwest::Error> for WeatherError {
     fn from(error: reqwest::Error) -> Self {
         Self::HttpError(error.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{rejection_reason, WeatherError};
+
+    #[test]
+    fn rejection_reason_taken_from_body() {
+        let openweather = WeatherError::HttpError(
+            r#"{"cod":401,"message":"Invalid API key. Please see https://openweathermap.org/faq#error401 for more info."}"#
+                .to_owned(),
+        );
+        assert_eq!(
+            "Invalid API key. Please see https://openweathermap.org/faq#error401 for more info.",
+            rejection_reason(&openweather)
+        );
+        let accuweather = WeatherError::HttpError(
+            r#"{"Code":"Unauthorized","Message":"Api Authorization failed"}"#.to_owned(),
+        );
+        assert_eq!("Api Authorization failed", rejection_reason(&accuweather));
+        assert_eq!(
+            "Failed to execute http request. Service Unavailable",
+            rejection_reason(&WeatherError::HttpError("Service Unavailable".to_owned()))
+        );
+        assert_eq!(
+            "No API_KEY provided",
+            rejection_reason(&WeatherError::NoApiKeyError)
+        );
+    }
+}
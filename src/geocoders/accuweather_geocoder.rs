@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::geocoder_api::{Geocoder, Location},
+    error::WeatherError,
+    http_client::{url_with_params, HttpClient},
+};
+
+/// Key of Accuweather's location key in [`Location::provider_ids`].
+pub const ACCUWEATHER_ID: &str = "accuweather";
+
+/// [`Geocoder`] implementation backed by Accuweather's Locations API. Needs Accuweather's API_KEY.
+#[derive(Debug, Clone)]
+pub struct AccuweatherGeocoder {
+    client: HttpClient,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Area {
+    #[serde(rename = "ID")]
+    id: String,
+    localized_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TimeZone {
+    name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GeoPosition {
+    latitude: f32,
+    longitude: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct City {
+    key: String,
+    localized_name: String,
+    country: Option<Area>,
+    administrative_area: Option<Area>,
+    time_zone: Option<TimeZone>,
+    geo_position: GeoPosition,
+}
+
+impl From<City> for Location {
+    fn from(city: City) -> Self {
+        Location {
+            name: city.localized_name,
+            lat: city.geo_position.latitude,
+            lon: city.geo_position.longitude,
+            country: city.country.map(|country| country.id),
+            state: city.administrative_area.map(|area| area.localized_name),
+            timezone: city.time_zone.map(|time_zone| time_zone.name),
+            provider_ids: [(ACCUWEATHER_ID.to_owned(), city.key)].into(),
+        }
+    }
+}
+
+impl AccuweatherGeocoder {
+    pub fn new(client: HttpClient, base_url: &str, api_key: Option<&str>) -> Self {
+        AccuweatherGeocoder {
+            client,
+            base_url: base_url.to_owned(),
+            api_key: api_key.map(str::to_owned),
+        }
+    }
+}
+
+impl Geocoder for AccuweatherGeocoder {
+    fn get_name(&self) -> String {
+        "Accuweather".to_owned()
+    }
+
+    fn search(&self, address: &str) -> Result<Vec<Location>, WeatherError> {
+        let url = url_with_params(
+            &format!("{}/locations/v1/cities/search", self.base_url),
+            &[
+                (
+                    "apikey",
+                    self.api_key.as_ref().ok_or(WeatherError::NoApiKeyError)?,
+                ),
+                ("q", address),
+            ],
+        )?;
+        let cities: Vec<City> = self.client.get_json(&url)?;
+        Ok(cities.into_iter().map(Location::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccuweatherGeocoder, ACCUWEATHER_ID};
+    use crate::{api::geocoder_api::Geocoder, error::WeatherError, http_client::HttpClient};
+
+    fn replaying(scenario: &str) -> AccuweatherGeocoder {
+        AccuweatherGeocoder::new(
+            HttpClient::replaying(&format!("accuweather/{scenario}")),
+            "http://dataservice.accuweather.com",
+            Some("somekey"),
+        )
+    }
+
+    #[test]
+    fn recorded_location_ok() {
+        let locations = replaying("geocoding").search("Lviv").unwrap();
+        assert_eq!(1, locations.len());
+        let location = &locations[0];
+        assert_eq!("Lviv", location.name);
+        assert_eq!((49.842, 24.032), (location.lat, location.lon));
+        assert_eq!(Some("UA".to_owned()), location.country);
+        assert_eq!(Some("Europe/Kiev".to_owned()), location.timezone);
+        assert_eq!(
+            Some(&"324561".to_owned()),
+            location.provider_ids.get(ACCUWEATHER_ID)
+        );
+    }
+
+    #[test]
+    fn recorded_nothing_found_ok() {
+        let locations = replaying("no_location").search("Nowhere").unwrap();
+        assert!(locations.is_empty());
+    }
+
+    #[test]
+    fn recorded_invalid_api_key_error_expected() {
+        match replaying("invalid_api_key").search("foo") {
+            Err(WeatherError::HttpError(body)) => {
+                assert!(body.contains("Api Authorization failed"))
+            }
+            other => panic!("Unexpected result {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,392 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    api::geocoder_api::{Geocoder, Location},
+    error::WeatherError,
+};
+
+/// Names of first level administrative areas GeoNames publishes along with cities files.
+const ADMIN1_FILE: &str = "admin1CodesASCII.txt";
+
+/// Most locations a search returns.
+const MAX_RESULTS: usize = 10;
+
+/// Geocoders of the cities files loaded so far, each file is read and indexed once per process.
+static LOADED: Mutex<BTreeMap<PathBuf, Arc<GeoNamesGeocoder>>> = Mutex::new(BTreeMap::new());
+
+/// City of GeoNames dump, only the columns locations need.
+#[derive(Debug, Clone)]
+struct City {
+    name: String,
+    lat: f32,
+    lon: f32,
+    country: String,
+    state: Option<String>,
+    /// GeoNames code of the state, e.g. `MO`, matched along with its name.
+    state_code: String,
+    timezone: Option<String>,
+    population: u64,
+}
+
+impl From<&City> for Location {
+    fn from(city: &City) -> Self {
+        Location {
+            name: city.name.clone(),
+            lat: city.lat,
+            lon: city.lon,
+            country: Some(city.country.clone()),
+            state: city.state.clone(),
+            timezone: city.timezone.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// [`Geocoder`] implementation backed by a GeoNames cities file, e.g. `cities15000.txt`.
+/// Resolves addresses with no network calls, hence no quota use.
+///
+/// Address is a city name, optionally followed by comma separated country code or state, e.g. `Springfield, MO`.
+/// Exact names go first, then the ones starting with the address, then the similar ones if nothing else is found.
+/// More populated cities go first among equal matches.
+#[derive(Debug, Clone)]
+pub struct GeoNamesGeocoder {
+    cities: Vec<City>,
+    /// Lowercase names, ASCII and alternate ones included, to indexes of cities.
+    names: BTreeMap<String, Vec<usize>>,
+}
+
+impl GeoNamesGeocoder {
+    /// Loads cities file. State names are taken from `admin1CodesASCII.txt` next to it if there is one,
+    /// state codes are used otherwise.
+    pub fn load(path: &Path) -> Result<Self, WeatherError> {
+        let cities = fs::read_to_string(path).map_err(|error| {
+            WeatherError::CitiesFileError(format!("{}: {error}", path.display()))
+        })?;
+        let admin1 = path
+            .parent()
+            .and_then(|dir| fs::read_to_string(dir.join(ADMIN1_FILE)).ok())
+            .unwrap_or_default();
+        GeoNamesGeocoder::parse(&cities, &admin1)
+    }
+
+    /// Parses tab separated GeoNames dumps and indexes city names.
+    fn parse(cities: &str, admin1: &str) -> Result<Self, WeatherError> {
+        // `US.IL	Illinois	Illinois	4896861`
+        let states: HashMap<&str, &str> = admin1
+            .lines()
+            .filter_map(|line| {
+                let mut columns = line.split('\t');
+                Some((columns.next()?, columns.next()?))
+            })
+            .collect();
+
+        let mut geocoder = GeoNamesGeocoder {
+            cities: vec![],
+            names: BTreeMap::new(),
+        };
+        for (number, line) in cities.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let columns: Vec<&str> = line.split('\t').collect();
+            let invalid =
+                || WeatherError::CitiesFileError(format!("Line {} is malformed", number + 1));
+            if columns.len() < 18 {
+                return Err(invalid());
+            }
+            let non_empty = |value: &str| (!value.is_empty()).then(|| value.to_owned());
+            let state_code = format!("{}.{}", columns[8], columns[10]);
+            let city = City {
+                name: columns[1].to_owned(),
+                lat: columns[4].parse().map_err(|_| invalid())?,
+                lon: columns[5].parse().map_err(|_| invalid())?,
+                country: columns[8].to_owned(),
+                state: states
+                    .get(state_code.as_str())
+                    .map(|state| state.to_string())
+                    .or_else(|| non_empty(columns[10])),
+                state_code: columns[10].to_owned(),
+                timezone: non_empty(columns[17]),
+                population: columns[14].parse().unwrap_or_default(),
+            };
+
+            let index = geocoder.cities.len();
+            let names = [columns[1], columns[2]]
+                .into_iter()
+                .chain(columns[3].split(','))
+                .filter(|name| !name.is_empty())
+                .map(str::to_lowercase);
+            for name in names {
+                let indexes = geocoder.names.entry(name).or_default();
+                if indexes.last() != Some(&index) {
+                    indexes.push(index);
+                }
+            }
+            geocoder.cities.push(city);
+        }
+        Ok(geocoder)
+    }
+
+    /// Whether city is in the country or the state the qualifier names, state by its code or name prefix.
+    fn qualifies(city: &City, qualifier: &str) -> bool {
+        city.country.eq_ignore_ascii_case(qualifier)
+            || city.state_code.eq_ignore_ascii_case(qualifier)
+            || city
+                .state
+                .as_ref()
+                .is_some_and(|state| state.to_lowercase().starts_with(qualifier))
+    }
+}
+
+/// Keeps the best rank of every city.
+fn rank(ranks: &mut HashMap<usize, usize>, indexes: &[usize], value: usize) {
+    for index in indexes {
+        let rank = ranks.entry(*index).or_insert(value);
+        *rank = value.min(*rank);
+    }
+}
+
+/// Levenshtein distance counted in chars.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+impl Geocoder for GeoNamesGeocoder {
+    fn get_name(&self) -> String {
+        "GeoNames".to_owned()
+    }
+
+    fn search(&self, address: &str) -> Result<Vec<Location>, WeatherError> {
+        let mut parts = address.split(',').map(|part| part.trim().to_lowercase());
+        let name = parts.next().unwrap_or_default();
+        let qualifiers: Vec<String> = parts.filter(|part| !part.is_empty()).collect();
+        if name.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // The best rank a city is matched with: exact name, prefix, similar names by their distance.
+        let mut ranks: HashMap<usize, usize> = HashMap::new();
+        for (key, indexes) in self
+            .names
+            .range(name.clone()..)
+            .take_while(|(key, _)| key.starts_with(&name))
+        {
+            rank(&mut ranks, indexes, usize::from(*key != name));
+        }
+        if ranks.is_empty() {
+            let length = name.chars().count();
+            let max_distance = if length <= 5 { 1 } else { 2 };
+            for (key, indexes) in &self.names {
+                if key.chars().count().abs_diff(length) <= max_distance {
+                    let distance = distance(&name, key);
+                    if distance <= max_distance {
+                        rank(&mut ranks, indexes, 1 + distance);
+                    }
+                }
+            }
+        }
+
+        let mut found: Vec<(usize, &City)> = ranks
+            .into_iter()
+            .map(|(index, rank)| (rank, &self.cities[index]))
+            .filter(|(_, city)| {
+                qualifiers
+                    .iter()
+                    .all(|qualifier| GeoNamesGeocoder::qualifies(city, qualifier))
+            })
+            .collect();
+        found.sort_by_key(|(rank, city)| (*rank, Reverse(city.population)));
+        Ok(found
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(_, city)| Location::from(city))
+            .collect())
+    }
+}
+
+/// [`GeoNamesGeocoder`] of the cities file loaded on the first search, so commands resolving no address,
+/// e.g. given coordinates or saved locations, never read it. Loaded ones are shared, see [`LOADED`].
+#[derive(Debug, Clone)]
+pub struct LazyGeoNamesGeocoder {
+    path: PathBuf,
+}
+
+impl LazyGeoNamesGeocoder {
+    pub fn new(path: &Path) -> Self {
+        LazyGeoNamesGeocoder {
+            path: path.to_owned(),
+        }
+    }
+
+    /// Geocoder of the file, loaded unless it is already. Searches wait while it loads.
+    fn geocoder(&self) -> Result<Arc<GeoNamesGeocoder>, WeatherError> {
+        let mut loaded = LOADED.lock().expect("Cities lock poisoned");
+        if let Some(geocoder) = loaded.get(&self.path) {
+            return Ok(geocoder.clone());
+        }
+        let geocoder = Arc::new(GeoNamesGeocoder::load(&self.path)?);
+        loaded.insert(self.path.clone(), geocoder.clone());
+        Ok(geocoder)
+    }
+}
+
+impl Geocoder for LazyGeoNamesGeocoder {
+    fn get_name(&self) -> String {
+        "GeoNames".to_owned()
+    }
+
+    fn search(&self, address: &str) -> Result<Vec<Location>, WeatherError> {
+        self.geocoder()?.search(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, sync::Arc};
+
+    use super::{distance, GeoNamesGeocoder, LazyGeoNamesGeocoder};
+    use crate::{api::geocoder_api::Geocoder, error::WeatherError};
+
+    fn geocoder() -> GeoNamesGeocoder {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/geonames/cities.txt");
+        GeoNamesGeocoder::load(&path).unwrap()
+    }
+
+    fn found(address: &str) -> Vec<String> {
+        geocoder()
+            .search(address)
+            .unwrap()
+            .into_iter()
+            .map(|location| format!("{}, {}", location.name, location.state.unwrap_or_default()))
+            .collect()
+    }
+
+    #[test]
+    fn exact_name_ok() {
+        let locations = geocoder().search("Lviv").unwrap();
+        assert_eq!(1, locations.len());
+        let lviv = &locations[0];
+        assert_eq!((49.83826, 24.02324), (lviv.lat, lviv.lon));
+        assert_eq!(Some("UA".to_owned()), lviv.country);
+        assert_eq!(Some("L'viv".to_owned()), lviv.state);
+        assert_eq!(Some("Europe/Kyiv".to_owned()), lviv.timezone);
+    }
+
+    #[test]
+    fn most_populated_go_first() {
+        assert_eq!(
+            vec![
+                "Springfield, Missouri",
+                "Springfield, Massachusetts",
+                "Springfield, Illinois",
+                "Springfield, Oregon"
+            ],
+            found("springfield")
+        );
+    }
+
+    #[test]
+    fn alternate_and_ascii_names_ok() {
+        assert_eq!(vec!["Kyiv, Kyiv City"], found("Kiev"));
+        assert_eq!(vec!["Zürich, Zurich"], found("Zurich"));
+        assert_eq!(vec!["Vienna, Vienna"], found("Wien"));
+    }
+
+    #[test]
+    fn prefix_ok() {
+        assert_eq!(4, found("Springf").len());
+        assert_eq!(vec!["Lviv, L'viv"], found("Lvi"));
+    }
+
+    #[test]
+    fn similar_names_ok() {
+        assert_eq!(vec!["Madrid, Madrid"], found("Madird"));
+        assert_eq!(vec!["London, England", "London, Ontario"], found("Londn"));
+        assert!(found("Nowhere").is_empty());
+    }
+
+    #[test]
+    fn country_and_state_qualifiers_ok() {
+        assert_eq!(vec!["Paris, Texas"], found("Paris, US"));
+        assert_eq!(vec!["Springfield, Missouri"], found("Springfield, mis"));
+        assert_eq!(vec!["London, Ontario"], found("London, CA, Ontario"));
+    }
+
+    #[test]
+    fn state_codes_matched_with_admin1_file() {
+        assert_eq!(vec!["Springfield, Missouri"], found("Springfield, MO"));
+        assert_eq!(vec!["Paris, Texas"], found("Paris, tx"));
+    }
+
+    #[test]
+    fn state_codes_used_without_admin1_file() {
+        let cities = std::fs::read_to_string(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/geonames/cities.txt"),
+        )
+        .unwrap();
+        let geocoder = GeoNamesGeocoder::parse(&cities, "").unwrap();
+        let locations = geocoder.search("Springfield, IL").unwrap();
+        assert_eq!(Some("IL".to_owned()), locations[0].state);
+    }
+
+    #[test]
+    fn malformed_file_error_expected() {
+        assert_eq!(
+            Err(WeatherError::CitiesFileError(
+                "Line 2 is malformed".to_owned()
+            )),
+            GeoNamesGeocoder::parse("\n1\tLviv\tLviv\n", "").map(|_| ())
+        );
+        assert!(matches!(
+            GeoNamesGeocoder::load(Path::new("no_such_cities.txt")),
+            Err(WeatherError::CitiesFileError(_))
+        ));
+    }
+
+    #[test]
+    fn distance_ok() {
+        assert_eq!(0, distance("lviv", "lviv"));
+        assert_eq!(1, distance("lviv", "lvov"));
+        assert_eq!(2, distance("madird", "madrid"));
+        assert_eq!(3, distance("kitten", "sitting"));
+    }
+
+    #[test]
+    fn lazily_loaded_once() {
+        let missing = LazyGeoNamesGeocoder::new(Path::new("no_such_cities.txt"));
+        assert!(matches!(
+            missing.search("Lviv"),
+            Err(WeatherError::CitiesFileError(_))
+        ));
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/geonames/cities.txt");
+        let (first, second) = (
+            LazyGeoNamesGeocoder::new(&path),
+            LazyGeoNamesGeocoder::new(&path),
+        );
+        assert!(Arc::ptr_eq(
+            &first.geocoder().unwrap(),
+            &second.geocoder().unwrap()
+        ));
+        assert_eq!(
+            geocoder().search("Springfield").unwrap(),
+            first.search("Springfield").unwrap()
+        );
+    }
+}
@@ -0,0 +1,4 @@
+pub mod openweather_geocoder;
+pub mod accuweather_geocoder;
+pub mod openmeteo_geocoder;
+pub mod geonames_geocoder;
\ No newline at end of file
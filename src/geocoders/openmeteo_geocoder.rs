@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::geocoder_api::{Geocoder, Location},
+    error::WeatherError,
+    http_client::{url_with_params, HttpClient},
+};
+
+/// [`Geocoder`] implementation backed by Open-Meteo's Geocoding API. Needs no API_KEY,
+/// the one of commercial plan is passed along if set.
+#[derive(Debug, Clone)]
+pub struct OpenMeteoGeocoder {
+    client: HttpClient,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Place {
+    name: String,
+    latitude: f32,
+    longitude: f32,
+    country_code: Option<String>,
+    admin1: Option<String>,
+    timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Places {
+    /// Missing if nothing is found.
+    #[serde(default)]
+    results: Vec<Place>,
+}
+
+impl From<Place> for Location {
+    fn from(place: Place) -> Self {
+        Location {
+            name: place.name,
+            lat: place.latitude,
+            lon: place.longitude,
+            country: place.country_code,
+            state: place.admin1,
+            timezone: place.timezone,
+            ..Default::default()
+        }
+    }
+}
+
+impl OpenMeteoGeocoder {
+    pub fn new(client: HttpClient, base_url: &str, api_key: Option<&str>) -> Self {
+        OpenMeteoGeocoder {
+            client,
+            base_url: base_url.to_owned(),
+            api_key: api_key.map(str::to_owned),
+        }
+    }
+}
+
+impl Geocoder for OpenMeteoGeocoder {
+    fn get_name(&self) -> String {
+        "Open-Meteo".to_owned()
+    }
+
+    fn search(&self, address: &str) -> Result<Vec<Location>, WeatherError> {
+        let mut params = vec![("name", address), ("count", "10"), ("format", "json")];
+        if let Some(api_key) = &self.api_key {
+            params.push(("apikey", api_key));
+        }
+        let url = url_with_params(&format!("{}/v1/search", self.base_url), &params)?;
+        let places: Places = self.client.get_json(&url)?;
+        Ok(places.results.into_iter().map(Location::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OpenMeteoGeocoder;
+    use crate::{api::geocoder_api::Geocoder, http_client::HttpClient, mock_server::MockServer};
+
+    fn replaying(scenario: &str) -> OpenMeteoGeocoder {
+        OpenMeteoGeocoder::new(
+            HttpClient::replaying(&format!("openmeteo/{scenario}")),
+            "https://geocoding-api.open-meteo.com",
+            None,
+        )
+    }
+
+    #[test]
+    fn recorded_location_ok() {
+        let locations = replaying("geocoding").search("Lviv").unwrap();
+        assert_eq!(1, locations.len());
+        let location = &locations[0];
+        assert_eq!("Lviv", location.name);
+        assert_eq!((49.83826, 24.02324), (location.lat, location.lon));
+        assert_eq!(Some("UA".to_owned()), location.country);
+        assert_eq!(Some("Lviv".to_owned()), location.state);
+        assert_eq!(Some("Europe/Kyiv".to_owned()), location.timezone);
+    }
+
+    #[test]
+    fn recorded_all_candidates_ok() {
+        let locations = replaying("ambiguous").search("Springfield").unwrap();
+        let states: Vec<_> = locations
+            .iter()
+            .filter_map(|location| location.state.as_deref())
+            .collect();
+        assert_eq!(
+            vec![
+                "Illinois",
+                "Missouri",
+                "Massachusetts",
+                "Oregon",
+                "Queensland"
+            ],
+            states
+        );
+    }
+
+    #[test]
+    fn recorded_nothing_found_ok() {
+        let locations = replaying("no_location").search("Nowhere").unwrap();
+        assert!(locations.is_empty());
+    }
+
+    #[test]
+    fn address_encoded_ok() {
+        let server = MockServer::start(&[("/v1/search", 200, "{}")]);
+        let geocoder = OpenMeteoGeocoder::new(HttpClient::default(), &server.url(), Some("a&b"));
+
+        assert!(geocoder.search("Tom & Jerry #1, Kyiv").unwrap().is_empty());
+        assert_eq!(
+            vec!["/v1/search?name=Tom+%26+Jerry+%231%2C+Kyiv&count=10&format=json&apikey=a%26b"],
+            server.requests()
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::geocoder_api::{Geocoder, Location},
+    error::WeatherError,
+    http_client::{url_with_params, HttpClient},
+};
+
+/// [`Geocoder`] implementation backed by OpenWeather's Geocoding API. Needs OpenWeather's API_KEY.
+#[derive(Debug, Clone)]
+pub struct OpenWeatherGeocoder {
+    client: HttpClient,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Place {
+    name: String,
+    lat: f32,
+    lon: f32,
+    country: Option<String>,
+    state: Option<String>,
+}
+
+impl From<Place> for Location {
+    fn from(place: Place) -> Self {
+        Location {
+            name: place.name,
+            lat: place.lat,
+            lon: place.lon,
+            country: place.country,
+            state: place.state,
+            ..Default::default()
+        }
+    }
+}
+
+impl OpenWeatherGeocoder {
+    pub fn new(client: HttpClient, base_url: &str, api_key: Option<&str>) -> Self {
+        OpenWeatherGeocoder {
+            client,
+            base_url: base_url.to_owned(),
+            api_key: api_key.map(str::to_owned),
+        }
+    }
+}
+
+impl Geocoder for OpenWeatherGeocoder {
+    fn get_name(&self) -> String {
+        "OpenWeather".to_owned()
+    }
+
+    fn search(&self, address: &str) -> Result<Vec<Location>, WeatherError> {
+        let url = url_with_params(
+            &format!("{}/geo/1.0/direct", self.base_url),
+            &[
+                ("q", address),
+                ("limit", "5"),
+                (
+                    "appid",
+                    self.api_key.as_ref().ok_or(WeatherError::NoApiKeyError)?,
+                ),
+            ],
+        )?;
+        let places: Vec<Place> = self.client.get_json(&url)?;
+        Ok(places.into_iter().map(Location::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OpenWeatherGeocoder;
+    use crate::{
+        api::geocoder_api::Geocoder, error::WeatherError, http_client::HttpClient,
+        mock_server::MockServer,
+    };
+
+    fn replaying(scenario: &str) -> OpenWeatherGeocoder {
+        OpenWeatherGeocoder::new(
+            HttpClient::replaying(&format!("openweather/{scenario}")),
+            "https://api.openweathermap.org",
+            Some("somekey"),
+        )
+    }
+
+    #[test]
+    fn recorded_location_ok() {
+        let locations = replaying("geocoding").search("Lviv").unwrap();
+        assert_eq!(1, locations.len());
+        let location = &locations[0];
+        assert_eq!("Lviv", location.name);
+        assert_eq!((49.8419, 24.0315), (location.lat, location.lon));
+        assert_eq!(Some("UA".to_owned()), location.country);
+        assert_eq!(Some("Lviv Oblast".to_owned()), location.state);
+    }
+
+    #[test]
+    fn recorded_all_candidates_ok() {
+        let locations = replaying("ambiguous").search("Springfield").unwrap();
+        let states: Vec<_> = locations
+            .iter()
+            .filter_map(|location| location.state.as_deref())
+            .collect();
+        assert_eq!(
+            vec![
+                "Illinois",
+                "Missouri",
+                "Massachusetts",
+                "Oregon",
+                "Queensland"
+            ],
+            states
+        );
+    }
+
+    #[test]
+    fn recorded_nothing_found_ok() {
+        let locations = replaying("no_location").search("Nowhere").unwrap();
+        assert!(locations.is_empty());
+    }
+
+    #[test]
+    fn base_url_used_ok() {
+        let server = MockServer::start(&[("/geo/1.0/direct", 200, "[]")]);
+        let geocoder =
+            OpenWeatherGeocoder::new(HttpClient::default(), &server.url(), Some("somekey"));
+
+        assert!(geocoder.search("Lviv").unwrap().is_empty());
+        assert_eq!(
+            vec!["/geo/1.0/direct?q=Lviv&limit=5&appid=somekey"],
+            server.requests()
+        );
+    }
+
+    #[test]
+    fn address_encoded_ok() {
+        let server = MockServer::start(&[("/geo/1.0/direct", 200, "[]")]);
+        let geocoder =
+            OpenWeatherGeocoder::new(HttpClient::default(), &server.url(), Some("somekey"));
+
+        assert!(geocoder.search("Lviv&limit=50").unwrap().is_empty());
+        assert_eq!(
+            vec!["/geo/1.0/direct?q=Lviv%26limit%3D50&limit=5&appid=somekey"],
+            server.requests()
+        );
+    }
+
+    #[test]
+    fn no_api_key_error_expected() {
+        let geocoder = OpenWeatherGeocoder::new(HttpClient::default(), "http://localhost", None);
+        assert_eq!(Err(WeatherError::NoApiKeyError), geocoder.search("Lviv"));
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use reqwest::blocking::Client;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::error::WeatherError;
+
+/// Directory to record request/response pairs into.
+static RECORD_ENV: &str = "WEATHER_HTTP_RECORD";
+
+/// Directory to replay request/response pairs from.
+static REPLAY_ENV: &str = "WEATHER_HTTP_REPLAY";
+
+/// Query parameters holding API keys. Their values never get into fixtures.
+const SECRET_PARAMS: [&str; 2] = ["apikey", "appid"];
+
+/// Response headers telling how many requests are left, lowercase. The only ones kept in fixtures.
+const QUOTA_HEADERS: [&str; 2] = ["ratelimit-remaining", "x-ratelimit-remaining"];
+
+/// What [`HttpClient`] does with requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// Sends requests over network.
+    Live,
+
+    /// Sends requests over network and saves request/response pairs as fixture files into the directory.
+    Record(PathBuf),
+
+    /// Serves responses from fixture files of the directory, never touches network.
+    Replay(PathBuf),
+}
+
+/// Request/response pair saved as a fixture file.
+#[derive(Debug, Serialize, Deserialize)]
+struct Fixture {
+    method: String,
+    /// Path and query with API keys redacted. Host is omitted so fixtures work with any base URL.
+    request: String,
+    status: u16,
+    /// Quota headers of the response, see [`QUOTA_HEADERS`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<String, String>,
+    /// JSON bodies are stored as is to keep fixtures readable, anything else as a string.
+    body: serde_json::Value,
+}
+
+/// Response as providers need it.
+struct Reply {
+    status: u16,
+    body: String,
+    /// Quota headers only, names are lowercase.
+    headers: BTreeMap<String, String>,
+}
+
+/// HTTP client providers send their requests with. Wraps sync implementation of reqwest
+/// and is able to record or replay request/response pairs, see [`Mode`].
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: Client,
+    mode: Mode,
+}
+
+/// Mode is taken from `WEATHER_HTTP_RECORD` or `WEATHER_HTTP_REPLAY` environment variables
+/// so providers deserialized from storage follow them as well.
+impl Default for HttpClient {
+    fn default() -> Self {
+        let mode = match (env::var_os(RECORD_ENV), env::var_os(REPLAY_ENV)) {
+            (_, Some(dir)) => Mode::Replay(dir.into()),
+            (Some(dir), None) => Mode::Record(dir.into()),
+            (None, None) => Mode::Live,
+        };
+        HttpClient::new(mode)
+    }
+}
+
+impl HttpClient {
+    pub fn new(mode: Mode) -> Self {
+        HttpClient {
+            client: Client::new(),
+            mode,
+        }
+    }
+
+    /// Executes GET request and parses json response. Non 200 response is turned into [`WeatherError::HttpError`].
+    pub fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, WeatherError> {
+        self.get_json_with_quota(url).map(|(value, _)| value)
+    }
+
+    /// Same as [`HttpClient::get_json`], also returns the number of requests left if the API reports it.
+    pub fn get_json_with_quota<T: DeserializeOwned>(
+        &self,
+        url: &str,
+    ) -> Result<(T, Option<u32>), WeatherError> {
+        let reply = self.get(url)?;
+        if reply.status != 200 {
+            return Err(WeatherError::HttpError(reply.body));
+        }
+        let quota = QUOTA_HEADERS
+            .iter()
+            .find_map(|name| reply.headers.get(*name)?.parse().ok());
+        let value = serde_json::from_str(&reply.body)
+            .map_err(|error| WeatherError::HttpError(error.to_string()))?;
+        Ok((value, quota))
+    }
+
+    /// Executes GET request returning status, body and quota headers of the response.
+    fn get(&self, url: &str) -> Result<Reply, WeatherError> {
+        match &self.mode {
+            Mode::Live => self.send(url),
+            Mode::Record(dir) => {
+                let reply = self.send(url)?;
+                let fixture = Fixture {
+                    method: "GET".to_owned(),
+                    request: fixture_request(url),
+                    status: reply.status,
+                    headers: reply.headers.clone(),
+                    body: serde_json::from_str(&reply.body)
+                        .unwrap_or(serde_json::Value::String(reply.body.clone())),
+                };
+                record(dir, &fixture).map_err(|error| {
+                    WeatherError::HttpError(format!("Failed to record fixture. {error}"))
+                })?;
+                Ok(reply)
+            }
+            Mode::Replay(dir) => {
+                let request = fixture_request(url);
+                let fixture: Fixture = fs::read_to_string(fixture_path(dir, &request))
+                    .ok()
+                    .and_then(|content| serde_json::from_str(&content).ok())
+                    .ok_or_else(|| {
+                        WeatherError::HttpError(format!("No fixture recorded for GET {request}"))
+                    })?;
+                let body = match fixture.body {
+                    serde_json::Value::String(body) => body,
+                    body => body.to_string(),
+                };
+                Ok(Reply {
+                    status: fixture.status,
+                    body,
+                    headers: fixture.headers,
+                })
+            }
+        }
+    }
+
+    fn send(&self, url: &str) -> Result<Reply, WeatherError> {
+        let response = self.client.get(url).send()?;
+        let headers = QUOTA_HEADERS
+            .iter()
+            .filter_map(|name| {
+                let value = response.headers().get(*name)?.to_str().ok()?;
+                Some((name.to_string(), value.to_owned()))
+            })
+            .collect();
+        Ok(Reply {
+            status: response.status().as_u16(),
+            headers,
+            body: response.text()?,
+        })
+    }
+}
+
+#[cfg(test)]
+impl HttpClient {
+    /// Client replaying fixtures of `fixtures/<scenario>` directory of the crate.
+    pub fn replaying(scenario: &str) -> Self {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(scenario);
+        HttpClient::new(Mode::Replay(dir))
+    }
+}
+
+/// URL of the endpoint with the query parameters encoded, so values such as user's address
+/// may hold `&`, `#` or non-ASCII characters.
+pub fn url_with_params(endpoint: &str, params: &[(&str, &str)]) -> Result<String, WeatherError> {
+    reqwest::Url::parse_with_params(endpoint, params)
+        .map(String::from)
+        .map_err(|error| WeatherError::HttpError(format!("{endpoint}: {error}")))
+}
+
+fn record(dir: &Path, fixture: &Fixture) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(
+        fixture_path(dir, &fixture.request),
+        serde_json::to_string_pretty(fixture)?,
+    )
+}
+
+/// Strips scheme and host off the URL and redacts API keys.
+fn fixture_request(url: &str) -> String {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let target = without_scheme
+        .find('/')
+        .map_or("/", |index| &without_scheme[index..]);
+    match target.split_once('?') {
+        Some((path, query)) => {
+            let query: Vec<String> = query
+                .split('&')
+                .map(|param| match param.split_once('=') {
+                    Some((name, _)) if SECRET_PARAMS.contains(&name) => format!("{name}=REDACTED"),
+                    _ => param.to_owned(),
+                })
+                .collect();
+            format!("{path}?{}", query.join("&"))
+        }
+        None => target.to_owned(),
+    }
+}
+
+/// Fixture file name is the readable path followed by a hash of the whole request.
+fn fixture_path(dir: &Path, request: &str) -> PathBuf {
+    let path = request.split('?').next().unwrap_or_default();
+    let readable: String = path
+        .trim_matches('/')
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    dir.join(format!("{readable}-{:016x}.json", fnv1a(request)))
+}
+
+/// FNV-1a hash, stable across Rust versions unlike the std one.
+fn fnv1a(value: &str) -> u64 {
+    value.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use crate::{error::WeatherError, mock_server::MockServer};
+
+    use super::{fixture_request, HttpClient, Mode};
+
+    #[test]
+    fn fixture_request_redacts_api_keys() {
+        assert_eq!(
+            "/geo/1.0/direct?q=Lviv&limit=1&appid=REDACTED",
+            fixture_request(
+                "https://api.openweathermap.org/geo/1.0/direct?q=Lviv&limit=1&appid=secret"
+            )
+        );
+        assert_eq!(
+            "/currentconditions/v1/324561?apikey=REDACTED&details=true",
+            fixture_request(
+                "http://127.0.0.1:8080/currentconditions/v1/324561?apikey=secret&details=true"
+            )
+        );
+        assert_eq!(
+            "/v1/search",
+            fixture_request("https://geocoding-api.open-meteo.com/v1/search")
+        );
+    }
+
+    #[test]
+    fn recorded_fixtures_replayed_ok() {
+        let dir = env::temp_dir().join(format!("weather-fixtures-{}", std::process::id()));
+        let server = MockServer::start(&[
+            ("/ok", 200, r#"{"value": 42}"#),
+            ("/unauthorized", 401, "Unauthorized"),
+        ]);
+
+        let recorder = HttpClient::new(Mode::Record(dir.clone()));
+        let value: serde_json::Value = recorder
+            .get_json(&format!("{}/ok?appid=secret", server.url()))
+            .unwrap();
+        assert_eq!(42, value["value"]);
+        let error =
+            recorder.get_json::<serde_json::Value>(&format!("{}/unauthorized", server.url()));
+        assert_eq!(
+            Err(WeatherError::HttpError("Unauthorized".to_owned())),
+            error
+        );
+
+        // Replaying needs neither the server nor the same API key.
+        let player = HttpClient::new(Mode::Replay(dir.clone()));
+        let value: serde_json::Value = player
+            .get_json("http://localhost/ok?appid=another")
+            .unwrap();
+        assert_eq!(42, value["value"]);
+        let error = player.get_json::<serde_json::Value>("http://localhost/unauthorized");
+        assert_eq!(
+            Err(WeatherError::HttpError("Unauthorized".to_owned())),
+            error
+        );
+        assert_eq!(
+            Err(WeatherError::HttpError(
+                "No fixture recorded for GET /missing".to_owned()
+            )),
+            player.get_json::<serde_json::Value>("http://localhost/missing")
+        );
+
+        let recorded = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(2, recorded);
+    }
+}
@@ -1,32 +1,125 @@
 mod api;
+mod comparison;
+mod concurrency;
+mod consensus;
+mod doctor;
 mod error;
+mod geocoders;
+mod http_client;
+#[cfg(test)]
+mod mock_server;
 mod providers;
+mod secrets;
 mod storage;
+mod units;
 
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+};
+
+use api::{
+    geocoder_api::{Geocoder, Location, LocationFilter},
+    provider_api::{api_key_env_var, env_api_key, WeatherForecast, WeatherProvider, WeatherReport},
+};
 use chrono::{Local, NaiveDate};
 use clap::{Parser, Subcommand};
-use error::WeatherError;
-use providers::{accuweather_api::AccuweatherProvider, openweather_api::OpenWeatherProvider};
-use storage::storage_api::Storage;
-
-use crate::storage::json_storage::JsonStorage;
+use comparison::Comparison;
+use concurrency::{run_all, DEFAULT_JOBS};
+use consensus::ConsensusReport;
+use doctor::HealthCheck;
+use error::{rejection_reason, WeatherError};
+use geocoders::geonames_geocoder::{GeoNamesGeocoder, LazyGeoNamesGeocoder};
+use providers::{
+    accuweather_api::AccuweatherProvider, openmeteo_api::OpenMeteoProvider,
+    openweather_api::OpenWeatherProvider,
+};
+use secrets::{key_file_path, Cipher, KeyProtection};
+use serde::Serialize;
+use storage::{
+    paths::{migrate, storage_path, CONFIG_VAR, STORAGE_FILE_NAME},
+    storage_api::Storage,
+};
+use units::UnitSystem;
 
-static JSON_STORAGE_FILE: &str = "json_storage.json";
+use crate::storage::{
+    json_storage::JsonStorage, sqlite_storage::SqliteStorage, toml_storage::TomlStorage,
+};
 
 #[derive(Parser)]
 #[clap(version, about = "Provides weather report for specified city. ")]
 struct Args {
     #[clap(subcommand)]
     command: Commands,
+
+    /// Most requests sent at once when several providers or addresses are asked. Configured one is used if omitted.
+    #[clap(long, global = true, value_name = "N")]
+    jobs: Option<usize>,
+
+    /// Configuration file to use. Taken from WEATHER_CONFIG if omitted, `weather/json_storage.json` under the config directory otherwise.
+    #[clap(long, global = true, value_name = "path")]
+    config: Option<PathBuf>,
+}
+
+/// Options narrowing down locations an ambiguous address is resolved to.
+#[derive(clap::Args)]
+struct LocationArgs {
+    /// Keep locations of the country only, e.g. US.
+    #[clap(long)]
+    country: Option<String>,
+
+    /// Keep locations whose state starts with the value, e.g. Illinois.
+    #[clap(long)]
+    state: Option<String>,
+
+    /// Take N-th of the matching locations instead of asking which one is meant.
+    #[clap(long, value_name = "N")]
+    pick: Option<usize>,
+}
+
+impl LocationArgs {
+    fn filter(&self) -> LocationFilter {
+        LocationFilter {
+            country: self.country.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// Outcome of a single address of `batch` command printed as JSON.
+#[derive(Serialize)]
+struct BatchEntry<'a> {
+    address: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report: Option<&'a WeatherReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
 }
 
 /// Commands supported by the app.
 #[derive(Subcommand)]
 enum Commands {
-    /// Configure weather's provider.
+    /// Configure weather's provider. API_KEY is prompted for unless one of its sources is given.
     Configure {
         #[clap(value_name = "provider")]
         provider_name: String,
+
+        /// API_KEY to save.
+        #[clap(long, value_name = "key", conflicts_with_all = &["api-key-stdin", "api-key-env"])]
+        api_key: Option<String>,
+
+        /// Read API_KEY from the first line of stdin.
+        #[clap(long, conflicts_with = "api-key-env")]
+        api_key_stdin: bool,
+
+        /// Take API_KEY from the environment variable.
+        #[clap(long, value_name = "VAR")]
+        api_key_env: Option<String>,
+
+        /// Save API_KEY without checking it with the provider, e.g. when offline.
+        #[clap(long)]
+        no_verify: bool,
     },
 
     /// Show the weather for the provided address.
@@ -35,9 +128,169 @@ enum Commands {
         #[clap(value_name = "address")]
         address: String,
 
-        /// Optional date weather is looked for.
-        #[clap(value_name = "date", parse(from_str=get_date), default_value = "now")]
+        /// Optional date weather is looked for, YYYY-MM-DD. Past dates are looked up in provider's history, future ones in its forecast.
+        #[clap(value_name = "date", parse(try_from_str=get_date), default_value = "now")]
         date: NaiveDate,
+
+        #[clap(flatten)]
+        location: LocationArgs,
+
+        /// Unit system of the report. Configured one is used if omitted.
+        #[clap(long, value_enum)]
+        units: Option<UnitSystem>,
+
+        /// Ask every configured provider and show the median, mean and spread of their values.
+        #[clap(long, alias = "consensus")]
+        all: bool,
+
+        /// Print the report as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Show reports of every configured provider side by side.
+    Compare {
+        /// Address the weather is looked for
+        #[clap(value_name = "address")]
+        address: String,
+
+        /// Optional date weather is looked for, YYYY-MM-DD.
+        #[clap(value_name = "date", parse(try_from_str=get_date), default_value = "now")]
+        date: NaiveDate,
+
+        #[clap(flatten)]
+        location: LocationArgs,
+
+        /// Unit system of the reports. Configured one is used if omitted.
+        #[clap(long, value_enum)]
+        units: Option<UnitSystem>,
+
+        /// Print the reports as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Show reports for every address of the file, one per line. Names of saved locations may be used as well.
+    Batch {
+        /// File with the addresses, - reads them from stdin.
+        #[clap(value_name = "file")]
+        file: String,
+
+        /// Optional date weather is looked for, YYYY-MM-DD.
+        #[clap(value_name = "date", parse(try_from_str=get_date), default_value = "now")]
+        date: NaiveDate,
+
+        #[clap(flatten)]
+        location: LocationArgs,
+
+        /// Unit system of the reports. Configured one is used if omitted.
+        #[clap(long, value_enum)]
+        units: Option<UnitSystem>,
+
+        /// Print the reports as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Show the forecast for the provided address.
+    Forecast {
+        /// Address the forecast is looked for
+        #[clap(value_name = "address")]
+        address: String,
+
+        #[clap(flatten)]
+        location: LocationArgs,
+
+        /// Number of days, today included.
+        #[clap(long, default_value_t = 5)]
+        days: u8,
+
+        /// Show entries with finer than daily granularity as well.
+        #[clap(long)]
+        hourly: bool,
+
+        /// Unit system of the forecast. Configured one is used if omitted.
+        #[clap(long, value_enum)]
+        units: Option<UnitSystem>,
+
+        /// Print the forecast as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// List locations the address may refer to.
+    Locate {
+        /// Address the locations are looked for
+        #[clap(value_name = "address")]
+        address: String,
+
+        #[clap(flatten)]
+        location: LocationArgs,
+
+        /// Print the locations as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Manage locations saved under names, e.g. home or office. Their names may be used instead of an address.
+    Location {
+        #[clap(subcommand)]
+        command: LocationCommands,
+    },
+
+    /// Show or set the unit system reports are shown in.
+    Units {
+        /// The unit system the app will use by default.
+        #[clap(value_name = "system", value_enum)]
+        system: Option<UnitSystem>,
+    },
+
+    /// Show or set the base URL provider's requests are sent to.
+    Endpoint {
+        /// The provider the base URL is changed for.
+        #[clap(value_name = "provider")]
+        provider_name: String,
+
+        /// New base URL, e.g. a proxy or an enterprise endpoint.
+        #[clap(value_name = "url")]
+        url: Option<String>,
+
+        /// Restore the default base URL.
+        #[clap(long, conflicts_with = "url")]
+        reset: bool,
+    },
+
+    /// Show or set the provider whose geocoder resolves addresses for every provider.
+    Geocoder {
+        /// The provider the geocoder of is used. Each provider uses its own if not set.
+        #[clap(value_name = "provider")]
+        provider_name: Option<String>,
+
+        /// Resolve addresses offline with GeoNames cities file, e.g. cities15000.txt.
+        #[clap(long, value_name = "file", conflicts_with = "provider-name")]
+        cities: Option<String>,
+
+        /// Let each provider use its own geocoder.
+        #[clap(long, conflicts_with_all = &["provider-name", "cities"])]
+        reset: bool,
+    },
+
+    /// Show or set providers tried in turn when the default one fails, e.g. runs out of quota.
+    Fallback {
+        /// Providers in the order they are tried.
+        #[clap(value_name = "provider")]
+        provider_names: Vec<String>,
+
+        /// Use the default provider only.
+        #[clap(long, conflicts_with = "provider-names")]
+        reset: bool,
+    },
+
+    /// Check every provider: API_KEY, a request with it, its round trip and the quota left. Fails if any check fails.
+    Doctor {
+        /// Print the checks as JSON.
+        #[clap(long)]
+        json: bool,
     },
 
     /// Set the default provider
@@ -47,36 +300,492 @@ enum Commands {
         provider_name: String,
     },
 
+    /// Manage encryption of saved API keys.
+    Key {
+        #[clap(subcommand)]
+        command: KeyCommands,
+    },
+
     /// List available providers.
     List,
 }
 
-fn get_date(date_string: &str) -> NaiveDate {
-    match NaiveDate::parse_from_str(date_string, "%Y-%m-%d") {
-        Ok(date) => date,
-        Err(_) => Local::now().date_naive(),
+impl Commands {
+    /// Whether the command reads saved API keys, so the passphrase is asked for if they are protected with one.
+    fn reads_api_keys(&self) -> bool {
+        !matches!(
+            self,
+            Commands::List
+                | Commands::Units { .. }
+                | Commands::Endpoint { .. }
+                | Commands::Geocoder { .. }
+                | Commands::Fallback { .. }
+                | Commands::Default { .. }
+                | Commands::Location {
+                    command: LocationCommands::List | LocationCommands::Delete { .. }
+                }
+        )
+    }
+}
+
+/// Commands of the location book.
+#[derive(Subcommand)]
+enum LocationCommands {
+    /// Save the location the address or coordinates are resolved to under the name.
+    Add {
+        /// Name to use instead of the address, e.g. home.
+        #[clap(value_name = "name")]
+        name: String,
+
+        /// Address or coordinates of the location.
+        #[clap(value_name = "address")]
+        address: String,
+
+        #[clap(flatten)]
+        location: LocationArgs,
+    },
+
+    /// List saved locations.
+    List,
+
+    /// Delete saved location.
+    Delete {
+        /// Name of the location.
+        #[clap(value_name = "name")]
+        name: String,
+    },
+}
+
+/// Commands of API keys encryption.
+#[derive(Subcommand)]
+enum KeyCommands {
+    /// Show provider's API_KEY in full. Asks for the passphrase, keys have to be protected with one.
+    Show {
+        #[clap(value_name = "provider")]
+        provider_name: String,
+    },
+
+    /// Protect saved API keys with a passphrase asked for whenever they are read.
+    Passphrase,
+
+    /// Protect saved API keys with the key file under the config directory, the default.
+    File,
+}
+
+fn get_date(date_string: &str) -> Result<NaiveDate, String> {
+    match date_string {
+        "now" | "today" => Ok(Local::now().date_naive()),
+        _ => NaiveDate::parse_from_str(date_string, "%Y-%m-%d")
+            .map_err(|_| format!("{date_string} is not a date of YYYY-MM-DD format")),
+    }
+}
+
+/// Offline geocoder if cities file is configured, geocoder of the configured provider otherwise.
+/// Provider's own one if neither is configured.
+fn get_geocoder(
+    storage: &mut Box<dyn Storage>,
+    provider: &dyn WeatherProvider,
+) -> Result<Box<dyn Geocoder>, WeatherError> {
+    if let Some(cities_file) = &storage.get_settings().cities_file {
+        return Ok(Box::new(LazyGeoNamesGeocoder::new(Path::new(cities_file))));
+    }
+    match storage.get_settings().geocoder.clone() {
+        Some(name) => storage
+            .get(&name)
+            .map(|geocoder_provider| geocoder_provider.get_geocoder())
+            .ok_or(WeatherError::NoSuchProviderError),
+        None => Ok(provider.get_geocoder()),
+    }
+}
+
+/// Locations the address may refer to, narrowed down by the options.
+/// Coordinates are taken as they are, geocoder is not asked.
+fn find_locations(
+    geocoder: &dyn Geocoder,
+    address: &str,
+    args: &LocationArgs,
+) -> Result<Vec<Location>, WeatherError> {
+    if let Some(location) = Location::from_coordinates(address)? {
+        return Ok(vec![location]);
+    }
+    let mut locations = args.filter().apply(geocoder.search(address)?);
+    match args.pick {
+        Some(pick) if pick == 0 || pick > locations.len() => {
+            Err(WeatherError::LocationPickError(pick, locations.len()))
+        }
+        Some(pick) => Ok(vec![locations.swap_remove(pick - 1)]),
+        None => Ok(locations),
+    }
+}
+
+/// Resolves the address to a single location. If there are several, asks which one is meant
+/// if `interactive`, takes the best match otherwise.
+fn locate(
+    geocoder: &dyn Geocoder,
+    address: &str,
+    args: &LocationArgs,
+    interactive: bool,
+) -> Result<Location, WeatherError> {
+    let mut locations = find_locations(geocoder, address, args)?;
+    if locations.len() > 1 {
+        if interactive {
+            let index = choose_location(&locations)?;
+            return Ok(locations.swap_remove(index));
+        }
+        eprintln!(
+            "{} locations match {address}, using the first one. Narrow them down with --country, --state or --pick.",
+            locations.len()
+        );
+    }
+    locations
+        .into_iter()
+        .next()
+        .ok_or(WeatherError::NoLocationFoundError)
+}
+
+/// Asks which of the locations is meant. Empty reply picks the first one.
+fn choose_location(locations: &[Location]) -> Result<usize, WeatherError> {
+    for (index, location) in locations.iter().enumerate() {
+        eprintln!("{}. {location}", index + 1);
+    }
+    loop {
+        let reply = rprompt::prompt_reply_stderr(&format!(
+            "Pick a location [1-{}, default 1]: ",
+            locations.len()
+        ))?;
+        if reply.trim().is_empty() {
+            return Ok(0);
+        }
+        match reply.trim().parse::<usize>() {
+            Ok(pick) if (1..=locations.len()).contains(&pick) => return Ok(pick - 1),
+            _ => eprintln!("{reply} is not a number of the listed locations"),
+        }
+    }
+}
+
+/// Location saved under the name, otherwise the one the address is resolved to.
+fn resolve_location(
+    storage: &mut Box<dyn Storage>,
+    provider: &dyn WeatherProvider,
+    address: &str,
+    args: &LocationArgs,
+) -> Result<Location, WeatherError> {
+    if let Some(location) = storage.get_location(address) {
+        return Ok(location.clone());
+    }
+    let geocoder = get_geocoder(storage, provider)?;
+    locate(geocoder.as_ref(), address, args, io::stdin().is_terminal())
+}
+
+/// Lines of the forecast, days and hours are labeled in the location's time.
+fn forecast_lines(forecast: &WeatherForecast, hourly: bool) -> Vec<String> {
+    let mut lines: Vec<String> = forecast
+        .daily
+        .iter()
+        .map(|entry| format!("{}: {entry}", forecast.local_time(entry).date_naive()))
+        .collect();
+    if hourly {
+        lines.extend(forecast.hourly.iter().map(|entry| {
+            format!(
+                "{}: {entry}",
+                forecast.local_time(entry).format("%Y-%m-%d %H:%M")
+            )
+        }));
+    }
+    lines
+}
+
+/// Names of providers to try: the default one followed by the fallback ones, each once.
+fn fallback_chain(default_name: &str, fallback: &[String]) -> Vec<String> {
+    let mut chain = vec![default_name.to_owned()];
+    for name in fallback {
+        if !chain.contains(name) {
+            chain.push(name.clone());
+        }
+    }
+    chain
+}
+
+/// Errors the next provider of the fallback chain is tried on. The others, e.g. a date no provider
+/// may serve or an address nothing is found for, would fail the same way.
+fn is_fallback_error(error: &WeatherError) -> bool {
+    matches!(
+        error,
+        WeatherError::HttpError(_) | WeatherError::NoReportFoundError | WeatherError::NoApiKeyError
+    )
+}
+
+/// Asks the default provider and, if it fails, the fallback ones in turn. Returns the one which answered
+/// along with the location and its answer. The address is resolved once, by the first provider able to.
+fn with_fallback<T>(
+    storage: &mut Box<dyn Storage>,
+    default_provider: Box<dyn WeatherProvider>,
+    address: &str,
+    args: &LocationArgs,
+    request: impl Fn(&dyn WeatherProvider, &Location) -> Result<T, WeatherError>,
+) -> Result<(Box<dyn WeatherProvider>, Location, T), WeatherError> {
+    let default_name = default_provider.get_name();
+    let mut fallbacks = vec![];
+    for name in &fallback_chain(&default_name, &storage.get_settings().fallback)[1..] {
+        match storage.get(name).cloned() {
+            Some(provider) => fallbacks.push(provider),
+            None => eprintln!("Warning: fallback provider {name} is not configured"),
+        }
+    }
+
+    let mut fallbacks = fallbacks.into_iter();
+    let mut provider = default_provider;
+    let mut location: Option<Location> = None;
+    loop {
+        let result = match location.clone() {
+            Some(location) => Ok(location),
+            None => resolve_location(storage, provider.as_ref(), address, args),
+        }
+        .and_then(|resolved| {
+            location = Some(resolved.clone());
+            request(provider.as_ref(), &resolved)
+        });
+        match result {
+            Ok(answer) => {
+                if provider.get_name() != default_name {
+                    eprintln!("{} answered instead of {default_name}", provider.get_name());
+                }
+                return Ok((provider, location.unwrap_or_default(), answer));
+            }
+            Err(error) if is_fallback_error(&error) => {
+                eprintln!("Warning: {} failed. {error}", provider.get_name());
+                match fallbacks.next() {
+                    Some(next) => provider = next,
+                    None => return Err(error),
+                }
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Providers able to serve reports: having API_KEY or needing none.
+fn configured_providers(storage: &dyn Storage) -> Vec<Box<dyn WeatherProvider + '_>> {
+    storage
+        .get_all()
+        .into_iter()
+        .filter(|provider| provider.get_api_key().is_some() || !provider.requires_api_key())
+        .map(dyn_clone::clone_box)
+        .collect()
+}
+
+/// Asks every provider for the report at once. Failing providers are skipped with a warning,
+/// the error is returned only if none answered.
+fn fetch_reports(
+    providers: &[Box<dyn WeatherProvider + '_>],
+    location: &Location,
+    date: NaiveDate,
+    jobs: usize,
+) -> Result<Vec<(String, WeatherReport)>, WeatherError> {
+    let results = run_all(providers, jobs, |provider| {
+        provider.get_report(location, date)
+    });
+    let mut reports = vec![];
+    let mut last_error = WeatherError::NoApiKeyError;
+    for (provider, result) in providers.iter().zip(results) {
+        match result {
+            Ok(report) => reports.push((provider.get_name(), report)),
+            Err(error) => {
+                eprintln!("Warning: {} failed. {error}", provider.get_name());
+                last_error = error;
+            }
+        }
+    }
+    if reports.is_empty() {
+        return Err(last_error);
+    }
+    Ok(reports)
+}
+
+/// API_KEY from the option, stdin or the environment variable, prompted for if none is given.
+fn read_api_key(
+    provider_name: &str,
+    api_key: &Option<String>,
+    api_key_stdin: bool,
+    api_key_env: &Option<String>,
+) -> Result<String, WeatherError> {
+    let api_key = match (api_key, api_key_env) {
+        (Some(api_key), _) => api_key.clone(),
+        (None, Some(var)) => {
+            env::var(var).map_err(|_| WeatherError::MissingEnvVarError(var.clone()))?
+        }
+        (None, None) if api_key_stdin => {
+            let mut line = String::new();
+            io::stdin().read_line(&mut line)?;
+            line
+        }
+        (None, None) => rprompt::prompt_reply_stdout(&format!("{provider_name}'s API_KEY:"))?,
+    };
+    match api_key.trim() {
+        "" => Err(WeatherError::NoApiKeyError),
+        api_key => Ok(api_key.to_owned()),
     }
 }
 
-/// Init 2 default providers. Last added is considered as default one
+/// Provider's API_KEY in full. Refused unless keys are protected with a passphrase, which is asked for
+/// to unlock them, so anyone at the terminal can't read them.
+fn shown_api_key(
+    protection: Option<&KeyProtection>,
+    provider: &dyn WeatherProvider,
+) -> Result<String, WeatherError> {
+    if !protection.is_some_and(KeyProtection::needs_passphrase) {
+        return Err(WeatherError::PassphraseRequiredError);
+    }
+    provider.get_api_key().ok_or(WeatherError::NoApiKeyError)
+}
+
+/// Asks for a new passphrase twice unless it is taken from the environment.
+fn read_new_passphrase() -> Result<String, WeatherError> {
+    if let Ok(passphrase) = env::var(secrets::PASSPHRASE_VAR) {
+        return Ok(passphrase);
+    }
+    loop {
+        let passphrase = rpassword::prompt_password("New passphrase: ")?;
+        if passphrase.is_empty() {
+            eprintln!("Passphrase can not be empty");
+        } else if passphrase != rpassword::prompt_password("Repeat passphrase: ")? {
+            eprintln!("Passphrases do not match");
+        } else {
+            return Ok(passphrase);
+        }
+    }
+}
+
+/// Encrypts saved API keys with the cipher of the protection instead of the current one.
+fn protect_api_keys(
+    storage: &mut Box<dyn Storage>,
+    protection: KeyProtection,
+    cipher: Cipher,
+) -> Result<(), WeatherError> {
+    let api_keys: Vec<(String, String)> = storage
+        .get_all()
+        .iter()
+        .filter_map(|provider| {
+            let api_key = secrets::reveal(&provider.get_saved_api_key()?)?;
+            Some((provider.get_name(), api_key))
+        })
+        .collect();
+    secrets::unlock(Some(cipher));
+    for (name, api_key) in api_keys {
+        if let Some(provider) = storage.get(&name) {
+            provider.set_api_key(&api_key)?;
+        }
+    }
+    let mut settings = storage.get_settings().clone();
+    settings.key_protection = Some(protection);
+    storage.set_settings(settings)
+}
+
+/// Decrypts saved API keys for the run. Keys saved in plain text, e.g. by older versions or by hand, get encrypted,
+/// with the key file if keys are not protected yet. Passphrase is asked for only if the command reads the keys.
+fn unlock_api_keys(storage: &mut Box<dyn Storage>, reads_keys: bool) -> Result<(), WeatherError> {
+    let protection = storage.get_settings().key_protection.clone();
+    match &protection {
+        Some(protection) if protection.needs_passphrase() && !reads_keys => return Ok(()),
+        Some(KeyProtection::KeyFile) if !key_file_path().exists() => eprintln!(
+            "Key file {} is missing, a new one is created. API keys saved before have to be configured again.",
+            key_file_path().display()
+        ),
+        _ => {}
+    }
+    let plain_saved = storage.get_all().iter().any(|provider| {
+        provider
+            .get_saved_api_key()
+            .is_some_and(|api_key| !secrets::is_sealed(&api_key))
+    });
+    let cipher = protection
+        .clone()
+        .unwrap_or(KeyProtection::KeyFile)
+        .cipher(|| secrets::read_passphrase("Passphrase: "))?;
+    if protection.is_none() || plain_saved {
+        protect_api_keys(
+            storage,
+            protection.unwrap_or(KeyProtection::KeyFile),
+            cipher,
+        )
+    } else {
+        secrets::unlock(Some(cipher));
+        Ok(())
+    }
+}
+
+/// Storage of the file: TOML one for `.toml` files, SQLite one for `.db`, `.sqlite` and `.sqlite3` ones,
+/// JSON one otherwise.
+fn open_storage(path: &Path) -> Result<Box<dyn Storage>, WeatherError> {
+    let path_name = path.to_string_lossy();
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => Ok(Box::new(TomlStorage::new(&path_name)?)),
+        Some("db" | "sqlite" | "sqlite3") => Ok(Box::new(SqliteStorage::new(&path_name)?)),
+        _ => Ok(Box::new(JsonStorage::new(&path_name)?)),
+    }
+}
+
+/// Init built-in providers missing in storage. Open-Meteo needs no API_KEY so it becomes default one
+/// if there is none, this way the app works right after install.
 fn init_providers(storage: &mut Box<dyn Storage>) -> Result<(), WeatherError> {
     if storage.get_all().is_empty() {
         storage.add(Box::new(AccuweatherProvider::new("Accuweather", None)))?;
         storage.add(Box::new(OpenWeatherProvider::new("OpenWeather", None)))?;
     }
+    if storage.get("Open-Meteo").is_none() {
+        storage.add(Box::new(OpenMeteoProvider::new("Open-Meteo", None)))?;
+        if storage.get_default_entry().is_none() {
+            storage.set_default_entry("Open-Meteo")?;
+        }
+    }
     Ok(())
 }
 
 fn main() -> Result<(), WeatherError> {
-    let mut storage: Box<dyn Storage> = Box::new(JsonStorage::new(JSON_STORAGE_FILE)?);
+    let args = Args::parse();
+    let path = storage_path(args.config.as_deref());
+    // Only the default location takes over the file older versions kept in the working directory.
+    if args.config.is_none()
+        && env::var_os(CONFIG_VAR).is_none()
+        && migrate(Path::new(STORAGE_FILE_NAME), &path)?
+    {
+        eprintln!(
+            "Configuration is moved from {} to {}",
+            Path::new(STORAGE_FILE_NAME).display(),
+            path.display()
+        );
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut storage = open_storage(&path)?;
     init_providers(&mut storage)?;
 
-    match &Args::parse().command {
-        Commands::Configure { provider_name } => match storage.get(provider_name).cloned() {
+    unlock_api_keys(&mut storage, args.command.reads_api_keys())?;
+    let jobs = args
+        .jobs
+        .or(storage.get_settings().jobs)
+        .unwrap_or(DEFAULT_JOBS);
+
+    match &args.command {
+        Commands::Configure {
+            provider_name,
+            api_key,
+            api_key_stdin,
+            api_key_env,
+            no_verify,
+        } => match storage.get(provider_name).cloned() {
             Some(mut provider) => {
-                let api_key =
-                    rprompt::prompt_reply_stdout(&format!("{provider_name}'s API_KEY:")).unwrap();
-                provider.set_api_key(&api_key);
+                let api_key = read_api_key(provider_name, api_key, *api_key_stdin, api_key_env)?;
+                provider.set_api_key(&api_key)?;
+                if !*no_verify {
+                    provider.validate_credentials().map_err(|error| {
+                        WeatherError::ApiKeyRejectedError(format!(
+                            "{provider_name}: {}. The key is not saved, add --no-verify to save it anyway.",
+                            rejection_reason(&error).trim_end_matches('.')
+                        ))
+                    })?;
+                }
                 storage.add(provider)?;
                 println!("API_KEY changed for {provider_name}");
             }
@@ -85,15 +794,175 @@ fn main() -> Result<(), WeatherError> {
             }
         },
 
-        Commands::Get { address, date } => match storage.get_default_entry() {
-            Some(default_provider) => {
-                let report = default_provider.get_report(address, *date)?;
-                println!("{date}, {}: {report}", default_provider.get_name());
+        Commands::Get {
+            address,
+            date,
+            location,
+            units,
+            all,
+            json,
+        } => {
+            let units = units.unwrap_or(storage.get_settings().units);
+            match storage.get_default_entry().cloned() {
+                Some(default_provider) => {
+                    if *all {
+                        let location = resolve_location(
+                            &mut storage,
+                            default_provider.as_ref(),
+                            address,
+                            location,
+                        )?;
+                        // Same location for everyone, otherwise providers' geocoders may pick different places.
+                        let providers = configured_providers(storage.as_ref());
+                        let reports = fetch_reports(&providers, &location, *date, jobs)?;
+                        let consensus = ConsensusReport {
+                            location: Some(location.clone()),
+                            ..ConsensusReport::new(&reports, units)
+                        };
+                        if *json {
+                            println!("{}", serde_json::to_string_pretty(&consensus)?);
+                        } else {
+                            println!(
+                                "{date}, {} of {} providers ({}) for {location}:",
+                                consensus.providers.len(),
+                                providers.len(),
+                                consensus.providers.join(", ")
+                            );
+                            println!("{consensus}");
+                            if consensus.has_disagreement() {
+                                eprintln!("Warning: providers disagree, check the values flagged.");
+                            }
+                        }
+                        return Ok(());
+                    }
+                    let (provider, location, report) = with_fallback(
+                        &mut storage,
+                        default_provider,
+                        address,
+                        location,
+                        |provider, location| provider.get_report(location, *date),
+                    )?;
+                    let report = WeatherReport {
+                        location: Some(location.clone()),
+                        ..report.convert(units)
+                    };
+                    if *json {
+                        println!("{}", serde_json::to_string_pretty(&report)?);
+                    } else {
+                        println!("{date}, {} for {location}: {report}", provider.get_name());
+                    }
+                }
+                None => {
+                    eprintln!("Error: {}", WeatherError::NoDefaultProviderError)
+                }
             }
-            None => {
-                eprintln!("Error: {}", WeatherError::NoDefaultProviderError)
+        }
+
+        Commands::Compare {
+            address,
+            date,
+            location,
+            units,
+            json,
+        } => {
+            let units = units.unwrap_or(storage.get_settings().units);
+            match storage.get_default_entry().cloned() {
+                Some(default_provider) => {
+                    let location = resolve_location(
+                        &mut storage,
+                        default_provider.as_ref(),
+                        address,
+                        location,
+                    )?;
+                    let providers = configured_providers(storage.as_ref());
+                    let comparison = Comparison {
+                        location: Some(location.clone()),
+                        ..Comparison::new(
+                            &fetch_reports(&providers, &location, *date, jobs)?,
+                            units,
+                        )
+                    };
+                    if *json {
+                        println!("{}", serde_json::to_string_pretty(&comparison)?);
+                    } else {
+                        println!("{date}, {location}:");
+                        println!("{}", comparison.to_table(io::stdout().is_terminal()));
+                    }
+                }
+                None => {
+                    eprintln!("Error: {}", WeatherError::NoDefaultProviderError)
+                }
             }
-        },
+        }
+
+        Commands::Batch {
+            file,
+            date,
+            location,
+            units,
+            json,
+        } => {
+            let units = units.unwrap_or(storage.get_settings().units);
+            let content = if file == "-" {
+                io::read_to_string(io::stdin())?
+            } else {
+                fs::read_to_string(file)?
+            };
+            // Saved locations are looked up here, storage is not shared by threads.
+            let addresses: Vec<(&str, Option<Location>)> = content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|address| (address, storage.get_location(address).cloned()))
+                .collect();
+            match storage.get_default_entry().cloned() {
+                Some(default_provider) => {
+                    let geocoder = get_geocoder(&mut storage, default_provider.as_ref())?;
+                    // Geocoding and the report of each address go together, addresses go at once.
+                    let results = run_all(
+                        &addresses,
+                        jobs,
+                        |(address, saved)| -> Result<WeatherReport, WeatherError> {
+                            let location = match saved {
+                                Some(location) => location.clone(),
+                                None => locate(geocoder.as_ref(), address, location, false)?,
+                            };
+                            let report = default_provider.get_report(&location, *date)?;
+                            Ok(WeatherReport {
+                                location: Some(location),
+                                ..report.convert(units)
+                            })
+                        },
+                    );
+                    if *json {
+                        let entries: Vec<BatchEntry> = addresses
+                            .iter()
+                            .zip(&results)
+                            .map(|((address, _), result)| BatchEntry {
+                                address,
+                                report: result.as_ref().ok(),
+                                error: result.as_ref().err().map(|error| format!("{error:?}")),
+                            })
+                            .collect();
+                        println!("{}", serde_json::to_string_pretty(&entries)?);
+                    } else {
+                        for ((address, _), result) in addresses.iter().zip(results) {
+                            match result {
+                                Ok(report) => println!(
+                                    "{date}, {} for {}: {report}",
+                                    default_provider.get_name(),
+                                    report.location.as_ref().unwrap_or(&Location::default())
+                                ),
+                                Err(error) => eprintln!("Error: {address}. {error:?}"),
+                            }
+                        }
+                    }
+                }
+                None => {
+                    eprintln!("Error: {}", WeatherError::NoDefaultProviderError)
+                }
+            }
+        }
 
         Commands::List => {
             let default_provider_name = storage
@@ -102,20 +971,419 @@ fn main() -> Result<(), WeatherError> {
                 .unwrap_or_default();
             storage.get_all().iter().for_each(|provider| {
                 let is_default = provider.get_name().eq(&default_provider_name);
+                let api_key = match (provider.get_api_key(), provider.get_saved_api_key()) {
+                    (Some(api_key), _) if env_api_key(&provider.get_name()).is_some() => format!(
+                        "{} (from {})",
+                        secrets::mask(&api_key),
+                        api_key_env_var(&provider.get_name())
+                    ),
+                    (Some(api_key), _) => secrets::mask(&api_key),
+                    // Locked by passphrase or encrypted with a lost key file.
+                    (None, Some(_)) => "encrypted".to_owned(),
+                    (None, None) => "not set".to_owned(),
+                };
                 println!(
-                    "Provider: {}, API_KEY: {}, default: {is_default}",
-                    provider.get_name(),
-                    provider
-                        .get_api_key()
-                        .unwrap_or_else(|| "not set".to_owned())
+                    "Provider: {}, API_KEY: {api_key}, default: {is_default}",
+                    provider.get_name()
                 );
             });
         }
 
+        Commands::Forecast {
+            address,
+            location,
+            days,
+            hourly,
+            units,
+            json,
+        } => {
+            let units = units.unwrap_or(storage.get_settings().units);
+            match storage.get_default_entry().cloned() {
+                Some(default_provider) => {
+                    let (provider, location, forecast) = with_fallback(
+                        &mut storage,
+                        default_provider,
+                        address,
+                        location,
+                        |provider, location| provider.get_forecast(location, *days),
+                    )?;
+                    let forecast = WeatherForecast {
+                        location: Some(location.clone()),
+                        ..forecast.convert(units)
+                    };
+                    if *json {
+                        println!("{}", serde_json::to_string_pretty(&forecast)?);
+                    } else {
+                        println!("{} for {location}:", provider.get_name());
+                        for line in forecast_lines(&forecast, *hourly) {
+                            println!("{line}");
+                        }
+                    }
+                }
+                None => {
+                    eprintln!("Error: {}", WeatherError::NoDefaultProviderError)
+                }
+            }
+        }
+
+        Commands::Locate {
+            address,
+            location,
+            json,
+        } => match storage.get_default_entry().cloned() {
+            Some(default_provider) => {
+                let geocoder = get_geocoder(&mut storage, default_provider.as_ref())?;
+                let locations = find_locations(geocoder.as_ref(), address, location)?;
+                if *json {
+                    println!("{}", serde_json::to_string_pretty(&locations)?);
+                } else if locations.is_empty() {
+                    eprintln!("Error: {}", WeatherError::NoLocationFoundError);
+                } else {
+                    for (index, location) in locations.iter().enumerate() {
+                        match &location.timezone {
+                            Some(timezone) => println!("{}. {location}, {timezone}", index + 1),
+                            None => println!("{}. {location}", index + 1),
+                        }
+                    }
+                }
+            }
+            None => {
+                eprintln!("Error: {}", WeatherError::NoDefaultProviderError)
+            }
+        },
+
+        Commands::Location { command } => match command {
+            LocationCommands::Add {
+                name,
+                address,
+                location,
+            } => match storage.get_default_entry().cloned() {
+                Some(default_provider) => {
+                    let geocoder = get_geocoder(&mut storage, default_provider.as_ref())?;
+                    let mut location = locate(
+                        geocoder.as_ref(),
+                        address,
+                        location,
+                        io::stdin().is_terminal(),
+                    )?;
+                    if location.name.is_empty() {
+                        location.name = name.clone();
+                    }
+                    // Saved once, ids spare providers a lookup on every call.
+                    for provider in configured_providers(storage.as_ref()) {
+                        if let Err(error) = provider.resolve_location_ids(&mut location) {
+                            eprintln!(
+                                "Warning: {} could not look the location up. {error:?}",
+                                provider.get_name()
+                            );
+                        }
+                    }
+                    println!("{name}: {location}");
+                    storage.add_location(name, location)?;
+                }
+                None => {
+                    eprintln!("Error: {}", WeatherError::NoDefaultProviderError)
+                }
+            },
+            LocationCommands::List => {
+                for (name, location) in storage.get_locations() {
+                    println!("{name}: {location}");
+                }
+            }
+            LocationCommands::Delete { name } => {
+                storage.delete_location(name)?;
+                println!("Location {name} deleted");
+            }
+        },
+
+        Commands::Units { system } => match system {
+            Some(system) => {
+                let mut settings = storage.get_settings().clone();
+                settings.units = *system;
+                storage.set_settings(settings)?;
+                println!("Unit system changed to {system}");
+            }
+            None => println!("Unit system: {}", storage.get_settings().units),
+        },
+
+        Commands::Endpoint {
+            provider_name,
+            url,
+            reset,
+        } => match storage.get(provider_name).cloned() {
+            Some(mut provider) => {
+                if url.is_some() || *reset {
+                    provider.set_base_url(url.as_deref());
+                    println!(
+                        "Base URL changed for {provider_name}: {}",
+                        provider.get_base_url()
+                    );
+                    storage.add(provider)?;
+                } else {
+                    println!("{provider_name}: {}", provider.get_base_url());
+                }
+            }
+            None => {
+                eprintln!("Error: {}", WeatherError::NoSuchProviderError)
+            }
+        },
+
+        Commands::Geocoder {
+            provider_name,
+            cities,
+            reset,
+        } => {
+            if provider_name.is_some() || cities.is_some() || *reset {
+                if let Some(name) = provider_name {
+                    if storage.get(name).is_none() {
+                        eprintln!("Error: {}", WeatherError::NoSuchProviderError);
+                        return Ok(());
+                    }
+                }
+                if let Some(cities) = cities {
+                    // Fail early rather than on the next report.
+                    GeoNamesGeocoder::load(Path::new(cities))?;
+                }
+                let mut settings = storage.get_settings().clone();
+                settings.geocoder = provider_name.clone();
+                settings.cities_file = cities.clone();
+                storage.set_settings(settings)?;
+            }
+            let settings = storage.get_settings();
+            match (&settings.cities_file, &settings.geocoder) {
+                (Some(cities_file), _) => println!("Geocoder: offline, {cities_file}"),
+                (None, Some(name)) => println!("Geocoder: {name}"),
+                (None, None) => println!("Geocoder: each provider's own"),
+            }
+        }
+
+        Commands::Fallback {
+            provider_names,
+            reset,
+        } => {
+            if !provider_names.is_empty() || *reset {
+                if let Some(name) = provider_names
+                    .iter()
+                    .find(|name| storage.get(name).is_none())
+                {
+                    eprintln!("Error: {name}. {}", WeatherError::NoSuchProviderError);
+                    return Ok(());
+                }
+                let mut settings = storage.get_settings().clone();
+                settings.fallback = provider_names.clone();
+                storage.set_settings(settings)?;
+            }
+            let fallback = &storage.get_settings().fallback;
+            if fallback.is_empty() {
+                println!("Fallback: none, the default provider only");
+            } else {
+                println!("Fallback: {}", fallback.join(", "));
+            }
+        }
+
+        Commands::Doctor { json } => {
+            let mut providers: Vec<Box<dyn WeatherProvider>> = storage
+                .get_all()
+                .into_iter()
+                .map(dyn_clone::clone_box)
+                .collect();
+            providers.sort_by_key(|provider| provider.get_name());
+            let checks = run_all(&providers, jobs, |provider| {
+                HealthCheck::run(provider.as_ref())
+            });
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&checks)?);
+            } else {
+                for check in &checks {
+                    println!("{check}");
+                }
+            }
+            let failed = checks.iter().filter(|check| !check.passed).count();
+            if failed > 0 {
+                return Err(WeatherError::HealthCheckError(failed, checks.len()));
+            }
+        }
+
         Commands::Default { provider_name } => {
             storage.set_default_entry(provider_name)?;
         }
+
+        Commands::Key { command } => match command {
+            KeyCommands::Show { provider_name } => {
+                let protection = storage.get_settings().key_protection.clone();
+                match storage.get(provider_name) {
+                    Some(provider) => {
+                        println!("{}", shown_api_key(protection.as_ref(), provider.as_ref())?)
+                    }
+                    None => eprintln!("Error: {}", WeatherError::NoSuchProviderError),
+                }
+            }
+            KeyCommands::Passphrase => {
+                let (protection, cipher) = KeyProtection::with_passphrase(&read_new_passphrase()?)?;
+                protect_api_keys(&mut storage, protection, cipher)?;
+                println!("API keys are protected with the passphrase");
+            }
+            KeyCommands::File => {
+                let cipher =
+                    KeyProtection::KeyFile.cipher(|| secrets::read_passphrase("Passphrase: "))?;
+                protect_api_keys(&mut storage, KeyProtection::KeyFile, cipher)?;
+                println!("API keys are protected with {}", key_file_path().display());
+            }
+        },
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::FixedOffset;
+    use clap::CommandFactory;
+
+    use super::{
+        fallback_chain, find_locations, forecast_lines, is_fallback_error, shown_api_key, Args,
+        LocationArgs,
+    };
+    use crate::{
+        api::{
+            geocoder_api::{Geocoder, Location},
+            provider_api::{ForecastEntry, WeatherForecast},
+        },
+        error::WeatherError,
+        providers::openmeteo_api::OpenMeteoProvider,
+        secrets::KeyProtection,
+    };
+
+    /// Geocoder resolving any address to Springfields of the states.
+    #[derive(Debug)]
+    struct Springfields(Vec<&'static str>);
+
+    impl Geocoder for Springfields {
+        fn get_name(&self) -> String {
+            "Springfields".to_owned()
+        }
+
+        fn search(&self, _address: &str) -> Result<Vec<Location>, WeatherError> {
+            Ok(self
+                .0
+                .iter()
+                .map(|state| Location {
+                    name: "Springfield".to_owned(),
+                    country: Some("US".to_owned()),
+                    state: Some(state.to_string()),
+                    ..Default::default()
+                })
+                .collect())
+        }
+    }
+
+    fn args(state: Option<&str>, pick: Option<usize>) -> LocationArgs {
+        LocationArgs {
+            country: None,
+            state: state.map(str::to_owned),
+            pick,
+        }
+    }
+
+    fn states(locations: Vec<Location>) -> Vec<String> {
+        locations.into_iter().filter_map(|l| l.state).collect()
+    }
+
+    #[test]
+    fn cli_definition_ok() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn locations_narrowed_down_ok() {
+        let geocoder = Springfields(vec!["Illinois", "Missouri", "Massachusetts"]);
+
+        let found = find_locations(&geocoder, "Springfield", &args(None, None)).unwrap();
+        assert_eq!(vec!["Illinois", "Missouri", "Massachusetts"], states(found));
+
+        let found = find_locations(&geocoder, "Springfield", &args(Some("m"), Some(2))).unwrap();
+        assert_eq!(vec!["Massachusetts"], states(found));
+    }
+
+    #[test]
+    fn coordinates_not_geocoded() {
+        let geocoder = Springfields(vec!["Illinois"]);
+
+        let found = find_locations(&geocoder, "geo:39.8,-89.6", &args(None, None)).unwrap();
+        assert_eq!(1, found.len());
+        assert_eq!((39.8, -89.6), (found[0].lat, found[0].lon));
+        assert_eq!(None, found[0].state);
+    }
+
+    #[test]
+    fn pick_out_of_range_error_expected() {
+        let geocoder = Springfields(vec!["Illinois", "Missouri"]);
+        for pick in [0, 3] {
+            assert_eq!(
+                Err(WeatherError::LocationPickError(pick, 2)),
+                find_locations(&geocoder, "Springfield", &args(None, Some(pick)))
+            );
+        }
+    }
+
+    #[test]
+    fn fallback_chain_starts_with_default() {
+        let fallback = vec![
+            "OpenWeather".to_owned(),
+            "Accuweather".to_owned(),
+            "Open-Meteo".to_owned(),
+        ];
+        assert_eq!(
+            vec!["Accuweather", "OpenWeather", "Open-Meteo"],
+            fallback_chain("Accuweather", &fallback)
+        );
+        assert_eq!(vec!["Open-Meteo"], fallback_chain("Open-Meteo", &[]));
+    }
+
+    #[test]
+    fn fallback_on_provider_failures_only() {
+        assert!(is_fallback_error(&WeatherError::HttpError(
+            "The allowed number of requests has been exceeded.".to_owned()
+        )));
+        assert!(is_fallback_error(&WeatherError::NoReportFoundError));
+        assert!(is_fallback_error(&WeatherError::NoApiKeyError));
+        assert!(!is_fallback_error(&WeatherError::NoLocationFoundError));
+        assert!(!is_fallback_error(&WeatherError::LocationPickError(3, 2)));
+    }
+
+    #[test]
+    fn key_shown_only_with_passphrase() {
+        let provider = OpenMeteoProvider::new("Key show test", Some("0123456789abcdef"));
+        for protection in [None, Some(KeyProtection::KeyFile)] {
+            assert_eq!(
+                Err(WeatherError::PassphraseRequiredError),
+                shown_api_key(protection.as_ref(), &provider)
+            );
+        }
+        let (protection, _) = KeyProtection::with_passphrase("passphrase").unwrap();
+        assert_eq!(
+            Ok("0123456789abcdef".to_owned()),
+            shown_api_key(Some(&protection), &provider)
+        );
+    }
+
+    #[test]
+    fn forecast_shown_in_location_time() {
+        let entry = |time: &str| ForecastEntry {
+            time: time.parse().unwrap(),
+            report: Default::default(),
+        };
+        let forecast = WeatherForecast {
+            // 2022-09-15 and its 01:00 in UTC+3.
+            daily: vec![entry("2022-09-14T21:00:00Z")],
+            hourly: vec![entry("2022-09-14T22:00:00Z")],
+            location: None,
+            offset: FixedOffset::east_opt(3 * 3600),
+        };
+        let lines = forecast_lines(&forecast, true);
+        assert_eq!(2, lines.len());
+        assert!(lines[0].starts_with("2022-09-15: "));
+        assert!(lines[1].starts_with("2022-09-15 01:00: "));
+        assert_eq!(1, forecast_lines(&forecast, false).len());
+    }
+}
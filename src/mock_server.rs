@@ -0,0 +1,72 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+/// Minimal HTTP server tests point providers at instead of real services.
+/// Serves every request with the first route whose pattern the request target starts with.
+pub struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    /// Starts serving `routes` of (target pattern, status, body) in background.
+    pub fn start(routes: &[(&str, u16, &str)]) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let routes: Vec<(String, u16, String)> = routes
+            .iter()
+            .map(|(pattern, status, body)| (pattern.to_string(), *status, body.to_string()))
+            .collect();
+        let requests = Arc::new(Mutex::new(vec![]));
+
+        let received = requests.clone();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(&stream);
+                if reader.read_line(&mut request_line).is_err() {
+                    continue;
+                }
+                // Skip headers, requests have no body.
+                let mut line = String::new();
+                while reader.read_line(&mut line).map(|n| n > 2).unwrap_or(false) {
+                    line.clear();
+                }
+
+                let target = request_line
+                    .split_whitespace()
+                    .nth(1)
+                    .unwrap_or_default()
+                    .to_owned();
+                let (status, body) = routes
+                    .iter()
+                    .find(|(pattern, _, _)| target.starts_with(pattern))
+                    .map(|(_, status, body)| (*status, body.as_str()))
+                    .unwrap_or((404, r#"{"message": "Not found"}"#));
+                received.lock().unwrap().push(target);
+
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {status} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+            }
+        });
+
+        MockServer { url, requests }
+    }
+
+    /// Base URL of the server, e.g. `http://127.0.0.1:12345`.
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// Targets (path and query) of the requests received so far.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
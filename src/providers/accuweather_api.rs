@@ -1,30 +1,115 @@
-use chrono::NaiveDate;
-use reqwest::{blocking::Client, StatusCode};
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, TimeZone, Timelike, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    api::provider_api::{WeatherProvider, WeatherReport},
+    api::{
+        geocoder_api::{Geocoder, Location},
+        provider_api::{
+            ApiStatus, ForecastEntry, WeatherForecast, WeatherProvider, WeatherReport,
+            PROBE_LOCATION,
+        },
+    },
     error::WeatherError,
+    geocoders::accuweather_geocoder::{AccuweatherGeocoder, ACCUWEATHER_ID},
+    http_client::HttpClient,
+    secrets,
+    units::UnitSystem,
 };
 
+/// Daily forecast endpoints Accuweather provides, in days.
+const FORECAST_DAYS: [u8; 4] = [1, 5, 10, 15];
+
 /// [`WeatherProvider`] implementation for [`AccuweatherProvider`].
-/// Naïve and no historical data support.
+/// Naïve, historical data is available for the past 24 hours only.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AccuweatherProvider {
-    /// Using sync implementation of reqwest since we are all sync. No need to store as well.
+    /// Using sync client since we are all sync. No need to store as well.
     #[serde(skip)]
-    client: Client,
+    client: HttpClient,
     provider_name: String,
     api_key: Option<String>,
+    #[serde(default = "default_base_url")]
+    base_url: String,
+}
+
+fn default_base_url() -> String {
+    "http://dataservice.accuweather.com".to_owned()
 }
 
 impl AccuweatherProvider {
     pub fn new(provider_name: &str, api_key: Option<&str>) -> Self {
         AccuweatherProvider {
-            client: Client::new(),
+            client: HttpClient::default(),
             provider_name: provider_name.to_owned(),
             api_key: api_key.map(str::to_string),
+            base_url: default_base_url(),
+        }
+    }
+
+    /// Accuweather's endpoints take location key. It is known already if Accuweather resolved the location,
+    /// otherwise it is looked up by coordinates.
+    fn get_location_key(&self, location: &Location) -> Result<String, WeatherError> {
+        if let Some(key) = location.provider_ids.get(ACCUWEATHER_ID) {
+            return Ok(key.clone());
         }
+        let city: Option<LocationKey> = self.client.get_json(&format!(
+            "{}/locations/v1/cities/geoposition/search?apikey={}&q={},{}",
+            self.base_url,
+            self.get_api_key().ok_or(WeatherError::NoApiKeyError)?,
+            location.lat,
+            location.lon
+        ))?;
+        city.map(|city| city.key)
+            .ok_or(WeatherError::NoLocationFoundError)
+    }
+
+    fn get_current(&self, location_key: &str) -> Result<WeatherReport, WeatherError> {
+        let reports: Vec<CurrentConditions> = self.client.get_json(&format!(
+            "{}/currentconditions/v1/{}?apikey={}&details=true",
+            self.base_url,
+            location_key,
+            self.get_api_key().ok_or(WeatherError::NoApiKeyError)?
+        ))?;
+        reports
+            .first()
+            .map(WeatherReport::from)
+            .ok_or(WeatherError::NoReportFoundError)
+    }
+
+    fn get_historical(
+        &self,
+        location_key: &str,
+        date: NaiveDate,
+    ) -> Result<WeatherReport, WeatherError> {
+        let observations: Vec<CurrentConditions> = self.client.get_json(&format!(
+            "{}/currentconditions/v1/{}/historical/24?apikey={}&details=true",
+            self.base_url,
+            location_key,
+            self.get_api_key().ok_or(WeatherError::NoApiKeyError)?
+        ))?;
+        historical_report(&observations, date).ok_or(WeatherError::DateNotSupportedError(date))
+    }
+
+    fn get_forecast_for(
+        &self,
+        location_key: &str,
+        days: u8,
+    ) -> Result<DailyForecasts, WeatherError> {
+        let max_days = *FORECAST_DAYS.last().unwrap();
+        let period = FORECAST_DAYS
+            .into_iter()
+            .find(|period| days >= 1 && *period >= days)
+            .ok_or(WeatherError::ForecastDaysError(max_days))?;
+
+        self.client.get_json(&format!(
+            "{}/forecasts/v1/daily/{}day/{}?apikey={}&details=true&metric=true",
+            self.base_url,
+            period,
+            location_key,
+            self.get_api_key().ok_or(WeatherError::NoApiKeyError)?
+        ))
     }
 }
 
@@ -43,18 +128,224 @@ struct Metric {
     unit: String,
 }
 
-/// Internal representaion of temperature.
+/// Internal representaion of any measured value: temperature, pressure, wind speed etc.
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
-struct Temperature {
+struct Measurement {
     metric: Metric,
 }
 
-/// Internal json for temperature option.
+impl Metric {
+    /// Speeds are reported in km/h, converts them to m/s.
+    fn speed(&self) -> f32 {
+        match self.unit.as_str() {
+            "km/h" => self.value / 3.6,
+            _ => self.value,
+        }
+    }
+}
+
+impl Measurement {
+    fn speed(&self) -> f32 {
+        self.metric.speed()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct WindDirection {
+    degrees: u16,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
-struct TemperatureReport {
-    temperature: Temperature,
+struct Wind {
+    direction: Option<WindDirection>,
+    speed: Option<Measurement>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PrecipitationSummary {
+    past_hour: Option<Measurement>,
+}
+
+/// Internal json for current conditions requested with `details=true`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CurrentConditions {
+    temperature: Measurement,
+    real_feel_temperature: Option<Measurement>,
+    relative_humidity: Option<f32>,
+    pressure: Option<Measurement>,
+    wind: Option<Wind>,
+    cloud_cover: Option<f32>,
+    precipitation_summary: Option<PrecipitationSummary>,
+    weather_icon: Option<u32>,
+    weather_text: Option<String>,
+    epoch_time: Option<i64>,
+    local_observation_date_time: Option<DateTime<FixedOffset>>,
+}
+
+/// Builds a report for the date out of hourly observations of the past 24 hours.
+/// Conditions are taken from the observation closest to the local midday.
+fn historical_report(observations: &[CurrentConditions], date: NaiveDate) -> Option<WeatherReport> {
+    let observations: Vec<&CurrentConditions> = observations
+        .iter()
+        .filter(|o| o.local_observation_date_time.map(|t| t.date_naive()) == Some(date))
+        .collect();
+    let midday = observations.iter().min_by_key(|o| {
+        o.local_observation_date_time
+            .map(|t| (t.hour() as i32 - 12).abs())
+            .unwrap_or(i32::MAX)
+    })?;
+    let temperatures = observations.iter().map(|o| o.temperature.metric.value);
+    Some(WeatherReport {
+        temperature_min: temperatures.clone().reduce(f32::min),
+        temperature_max: temperatures.reduce(f32::max),
+        ..WeatherReport::from(*midday)
+    })
+}
+
+impl From<&CurrentConditions> for WeatherReport {
+    fn from(report: &CurrentConditions) -> Self {
+        let wind = report.wind.as_ref();
+        WeatherReport {
+            units: UnitSystem::Metric,
+            temperature: Some(report.temperature.metric.value),
+            feels_like: report
+                .real_feel_temperature
+                .as_ref()
+                .map(|temperature| temperature.metric.value),
+            temperature_min: None,
+            temperature_max: None,
+            humidity: report.relative_humidity,
+            pressure: report
+                .pressure
+                .as_ref()
+                .map(|pressure| pressure.metric.value),
+            wind_speed: wind.and_then(|w| w.speed.as_ref()).map(Measurement::speed),
+            wind_direction: wind.and_then(|w| w.direction.as_ref()).map(|d| d.degrees),
+            cloud_cover: report.cloud_cover,
+            precipitation: report
+                .precipitation_summary
+                .as_ref()
+                .and_then(|summary| summary.past_hour.as_ref())
+                .map(|past_hour| past_hour.metric.value),
+            condition_code: report.weather_icon,
+            description: report.weather_text.clone(),
+            observed_at: report
+                .epoch_time
+                .and_then(|epoch| Utc.timestamp_opt(epoch, 0).single()),
+            location: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TemperatureRange {
+    minimum: Metric,
+    maximum: Metric,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DailyWind {
+    direction: Option<WindDirection>,
+    speed: Option<Metric>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Humidity {
+    average: Option<f32>,
+}
+
+/// Day or night part of a daily forecast.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DayPart {
+    icon: Option<u32>,
+    icon_phrase: Option<String>,
+    wind: Option<DailyWind>,
+    total_liquid: Option<Metric>,
+    cloud_cover: Option<f32>,
+    relative_humidity: Option<Humidity>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DailyForecast {
+    /// Local time the forecast is for, it tells the location's shift from UTC.
+    date: Option<DateTime<FixedOffset>>,
+    epoch_date: i64,
+    temperature: TemperatureRange,
+    day: Option<DayPart>,
+    night: Option<DayPart>,
+}
+
+/// Internal json for daily forecasts requested with `details=true` and `metric=true`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DailyForecasts {
+    daily_forecasts: Vec<DailyForecast>,
+}
+
+impl DailyForecasts {
+    /// Shift of the location's time from UTC, UTC itself if it is unknown.
+    fn offset(&self) -> FixedOffset {
+        self.daily_forecasts
+            .iter()
+            .find_map(|forecast| forecast.date)
+            .map_or_else(|| FixedOffset::east_opt(0).unwrap(), |date| *date.offset())
+    }
+
+    fn into_forecast(self, days: u8) -> WeatherForecast {
+        WeatherForecast {
+            offset: Some(self.offset()),
+            daily: self
+                .daily_forecasts
+                .iter()
+                .take(days as usize)
+                .map(ForecastEntry::from)
+                .collect(),
+            hourly: vec![],
+            location: None,
+        }
+    }
+}
+
+impl From<&DailyForecast> for ForecastEntry {
+    fn from(forecast: &DailyForecast) -> Self {
+        let day = forecast.day.as_ref();
+        let wind = day.and_then(|d| d.wind.as_ref());
+        ForecastEntry {
+            time: Utc
+                .timestamp_opt(forecast.epoch_date, 0)
+                .single()
+                .unwrap_or_default(),
+            report: WeatherReport {
+                units: UnitSystem::Metric,
+                temperature_min: Some(forecast.temperature.minimum.value),
+                temperature_max: Some(forecast.temperature.maximum.value),
+                humidity: day
+                    .and_then(|d| d.relative_humidity.as_ref())
+                    .and_then(|h| h.average),
+                wind_speed: wind.and_then(|w| w.speed.as_ref()).map(Metric::speed),
+                wind_direction: wind.and_then(|w| w.direction.as_ref()).map(|d| d.degrees),
+                cloud_cover: day.and_then(|d| d.cloud_cover),
+                precipitation: [&forecast.day, &forecast.night]
+                    .iter()
+                    .filter_map(|part| part.as_ref().and_then(|p| p.total_liquid.as_ref()))
+                    .map(|liquid| liquid.value)
+                    .reduce(|a, b| a + b),
+                condition_code: day.and_then(|d| d.icon),
+                description: day.and_then(|d| d.icon_phrase.clone()),
+                ..Default::default()
+            },
+        }
+    }
 }
 
 #[typetag::serde]
@@ -63,67 +354,396 @@ impl WeatherProvider for AccuweatherProvider {
         self.provider_name.clone()
     }
 
-    fn get_api_key(&self) -> Option<String> {
+    fn get_saved_api_key(&self) -> Option<String> {
         self.api_key.clone()
     }
 
-    fn set_api_key(&mut self, api_key: &str) {
-        self.api_key = Some(api_key.to_owned());
+    fn set_api_key(&mut self, api_key: &str) -> Result<(), WeatherError> {
+        self.api_key = Some(secrets::conceal(api_key)?);
+        Ok(())
+    }
+
+    fn get_base_url(&self) -> String {
+        self.base_url.clone()
+    }
+
+    fn set_base_url(&mut self, base_url: Option<&str>) {
+        self.base_url = base_url.map_or_else(default_base_url, str::to_owned);
+    }
+
+    /// Location search is the cheapest authenticated request, it also reports the quota left.
+    fn validate_credentials(&self) -> Result<ApiStatus, WeatherError> {
+        let (lat, lon) = PROBE_LOCATION;
+        let (_, quota_remaining): (Option<LocationKey>, _) =
+            self.client.get_json_with_quota(&format!(
+                "{}/locations/v1/cities/geoposition/search?apikey={}&q={lat},{lon}",
+                self.base_url,
+                self.get_api_key().ok_or(WeatherError::NoApiKeyError)?
+            ))?;
+        Ok(ApiStatus { quota_remaining })
+    }
+
+    fn get_geocoder(&self) -> Box<dyn Geocoder> {
+        Box::new(AccuweatherGeocoder::new(
+            self.client.clone(),
+            &self.base_url,
+            self.get_api_key().as_deref(),
+        ))
+    }
+
+    fn resolve_location_ids(&self, location: &mut Location) -> Result<(), WeatherError> {
+        let key = self.get_location_key(location)?;
+        location.provider_ids.insert(ACCUWEATHER_ID.to_owned(), key);
+        Ok(())
     }
 
     // Accuweather seems to have unreasonably low call rates for free accout to test the call...
-    fn get_report(&self, address: &str, _date: NaiveDate) -> Result<WeatherReport, WeatherError> {
-        let response = self.client.get(format!("http://dataservice.accuweather.com/locations/v1/cities/autocomplete?apikey={}&q={}", self.get_api_key().ok_or(WeatherError::NoApiKeyError)?, address)).send()?;
-        if response.status() != StatusCode::OK {
-            return Err(WeatherError::HttpError(response.text()?));
+    fn get_report(
+        &self,
+        location: &Location,
+        date: NaiveDate,
+    ) -> Result<WeatherReport, WeatherError> {
+        let today = Local::now().date_naive();
+        let max_days = *FORECAST_DAYS.last().unwrap() as i64;
+        match date.cmp(&today) {
+            Ordering::Equal => self.get_current(&self.get_location_key(location)?),
+            Ordering::Less if date == today - Duration::days(1) => {
+                self.get_historical(&self.get_location_key(location)?, date)
+            }
+            Ordering::Greater if (date - today).num_days() < max_days => {
+                // The location's date may be a day behind the machine's one.
+                let days = ((date - today).num_days() + 2).min(max_days) as u8;
+                let forecasts = self.get_forecast_for(&self.get_location_key(location)?, days)?;
+                let offset = forecasts.offset();
+                forecasts
+                    .into_forecast(days)
+                    .into_day(offset, date)
+                    .map(|entry| entry.report)
+                    .ok_or(WeatherError::DateNotSupportedError(date))
+            }
+            _ => Err(WeatherError::DateNotSupportedError(date)),
         }
+    }
 
-        let locations: Vec<LocationKey> = response.json()?;
-
-        if let Some(location) = locations.get(0) {
-            let response = self
-                .client
-                .get(format!(
-                    "http://dataservice.accuweather.com/currentconditions/v1/{}?apikey={}",
-                    location.key,
-                    self.get_api_key().unwrap()
-                ))
-                .send()?;
-            let reports: Vec<TemperatureReport> = response.json()?;
-            if let Some(report) = reports.get(0) {
-                let report = format!(
-                    "{} {}",
-                    report.temperature.metric.value, report.temperature.metric.unit
-                );
-                Ok(WeatherReport { report })
-            } else {
-                Err(WeatherError::NoReportFoundError)
-            }
-        } else {
-            Err(WeatherError::NoLocationFoundError)
+    fn get_forecast(&self, location: &Location, days: u8) -> Result<WeatherForecast, WeatherError> {
+        if !FORECAST_DAYS
+            .iter()
+            .any(|period| (1..=*period).contains(&days))
+        {
+            return Err(WeatherError::ForecastDaysError(
+                *FORECAST_DAYS.last().unwrap(),
+            ));
         }
+        Ok(self
+            .get_forecast_for(&self.get_location_key(location)?, days)?
+            .into_forecast(days))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use chrono::Local;
+    use chrono::{Duration, Local, NaiveDate};
 
-    use crate::{api::provider_api::WeatherProvider, error::WeatherError};
+    use super::{historical_report, AccuweatherProvider, CurrentConditions, DailyForecasts};
+    use crate::{
+        api::{
+            geocoder_api::Location,
+            provider_api::{
+                api_key_env_var, api_key_in_use, ForecastEntry, WeatherProvider, WeatherReport,
+            },
+        },
+        error::WeatherError,
+        geocoders::accuweather_geocoder::ACCUWEATHER_ID,
+        http_client::HttpClient,
+        mock_server::MockServer,
+    };
+
+    static GEOPOSITION: &str =
+        r#"{"Version": 1, "Key": "324561", "Type": "City", "Rank": 30, "LocalizedName": "Lviv"}"#;
+
+    /// Lviv as resolved by Accuweather's geocoder, location key included.
+    fn lviv() -> Location {
+        Location {
+            name: "Lviv".to_owned(),
+            lat: 49.842,
+            lon: 24.032,
+            provider_ids: [(ACCUWEATHER_ID.to_owned(), "324561".to_owned())].into(),
+            ..Default::default()
+        }
+    }
+
+    fn test_provider(server: &MockServer) -> AccuweatherProvider {
+        let mut provider = AccuweatherProvider::new("testprovider", Some("somekey"));
+        provider.set_base_url(Some(&server.url()));
+        provider
+    }
+
+    static CURRENT_CONDITIONS: &str = r#"[{
+        "LocalObservationDateTime": "2022-09-15T12:00:00+03:00",
+        "EpochTime": 1663232400,
+        "WeatherText": "Cloudy",
+        "WeatherIcon": 7,
+        "Temperature": {"Metric": {"Value": 15.6, "Unit": "C", "UnitType": 17}},
+        "RealFeelTemperature": {"Metric": {"Value": 14.9, "Unit": "C", "UnitType": 17}},
+        "RelativeHumidity": 82,
+        "Wind": {"Direction": {"Degrees": 248, "Localized": "WSW"}, "Speed": {"Metric": {"Value": 14.4, "Unit": "km/h"}}},
+        "CloudCover": 90,
+        "Pressure": {"Metric": {"Value": 1012.0, "Unit": "mb"}},
+        "PrecipitationSummary": {"PastHour": {"Metric": {"Value": 0.2, "Unit": "mm"}}}
+    }]"#;
+
+    #[test]
+    fn report_fields_parsed_ok() {
+        let reports: Vec<CurrentConditions> = serde_json::from_str(CURRENT_CONDITIONS).unwrap();
+        let report: WeatherReport = reports.first().unwrap().into();
+        assert_eq!(Some(15.6), report.temperature);
+        assert_eq!(Some(14.9), report.feels_like);
+        assert_eq!(Some(82.0), report.humidity);
+        assert_eq!(Some(1012.0), report.pressure);
+        assert_eq!(Some(4.0), report.wind_speed);
+        assert_eq!(Some(248), report.wind_direction);
+        assert_eq!(Some(90.0), report.cloud_cover);
+        assert_eq!(Some(0.2), report.precipitation);
+        assert_eq!(Some(7), report.condition_code);
+        assert_eq!(Some("Cloudy".to_owned()), report.description);
+        assert_eq!(1663232400, report.observed_at.unwrap().timestamp());
+    }
 
-    use super::AccuweatherProvider;
+    static DAILY_FORECASTS: &str = r#"{
+        "Headline": {"Text": "Pleasant Friday"},
+        "DailyForecasts": [{
+            "Date": "2022-09-15T07:00:00+03:00",
+            "EpochDate": 1663214400,
+            "Temperature": {"Minimum": {"Value": 9.4, "Unit": "C"}, "Maximum": {"Value": 17.2, "Unit": "C"}},
+            "Day": {
+                "Icon": 3,
+                "IconPhrase": "Partly sunny",
+                "Wind": {"Speed": {"Value": 18.0, "Unit": "km/h"}, "Direction": {"Degrees": 270}},
+                "TotalLiquid": {"Value": 1.2, "Unit": "mm"},
+                "CloudCover": 45,
+                "RelativeHumidity": {"Minimum": 48, "Maximum": 80, "Average": 61}
+            },
+            "Night": {"Icon": 35, "IconPhrase": "Partly cloudy", "TotalLiquid": {"Value": 0.3, "Unit": "mm"}}
+        }]
+    }"#;
+
+    #[test]
+    fn location_key_looked_up_by_coordinates_ok() {
+        let server = MockServer::start(&[
+            ("/locations/v1/cities/geoposition/search", 200, GEOPOSITION),
+            ("/currentconditions/v1/324561?", 200, CURRENT_CONDITIONS),
+        ]);
+        let provider = test_provider(&server);
+        let location = Location {
+            provider_ids: Default::default(),
+            ..lviv()
+        };
+
+        let report = provider
+            .get_report(&location, Local::now().date_naive())
+            .unwrap();
+        assert_eq!(Some(15.6), report.temperature);
+        assert_eq!(
+            vec![
+                "/locations/v1/cities/geoposition/search?apikey=somekey&q=49.842,24.032",
+                "/currentconditions/v1/324561?apikey=somekey&details=true"
+            ],
+            server.requests()
+        );
+    }
+
+    /// Provider serving responses recorded into `fixtures/accuweather/<scenario>`.
+    fn replaying(scenario: &str) -> AccuweatherProvider {
+        let mut provider = AccuweatherProvider::new("testprovider", Some("somekey"));
+        provider.client = HttpClient::replaying(&format!("accuweather/{scenario}"));
+        provider
+    }
+
+    #[test]
+    fn location_key_resolved_ok() {
+        let server =
+            MockServer::start(&[("/locations/v1/cities/geoposition/search", 200, GEOPOSITION)]);
+        let provider = test_provider(&server);
+        let mut location = Location {
+            provider_ids: Default::default(),
+            ..lviv()
+        };
+
+        provider.resolve_location_ids(&mut location).unwrap();
+        assert_eq!(lviv(), location);
+
+        // Nothing is looked up once the key is known.
+        provider.resolve_location_ids(&mut location).unwrap();
+        assert_eq!(1, server.requests().len());
+    }
+
+    #[test]
+    fn recorded_current_report_ok() {
+        let report = replaying("current")
+            .get_report(&lviv(), Local::now().date_naive())
+            .unwrap();
+        assert_eq!(Some(15.6), report.temperature);
+        assert_eq!(Some(4.0), report.wind_speed);
+        assert_eq!(Some("Cloudy".to_owned()), report.description);
+    }
+
+    #[test]
+    fn recorded_forecast_ok() {
+        let forecast = replaying("forecast").get_forecast(&lviv(), 3).unwrap();
+        assert_eq!(3, forecast.daily.len());
+        assert!(forecast.hourly.is_empty());
+
+        let third = &forecast.daily[2].report;
+        assert_eq!(Some(11.3), third.temperature_min);
+        assert_eq!(Some(16.4), third.temperature_max);
+        assert_eq!(Some("Showers".to_owned()), third.description);
+    }
+
+    #[test]
+    fn recorded_quota_exceeded_error_expected() {
+        let result = replaying("quota_exceeded").get_forecast(&lviv(), 5);
+        match result {
+            Err(WeatherError::HttpError(body)) => {
+                assert!(body.contains("The allowed number of requests has been exceeded."))
+            }
+            other => panic!("Unexpected result {other:?}"),
+        }
+    }
+
+    #[test]
+    fn daily_forecast_parsed_ok() {
+        let forecasts: DailyForecasts = serde_json::from_str(DAILY_FORECASTS).unwrap();
+        let entry: ForecastEntry = forecasts.daily_forecasts.first().unwrap().into();
+        assert_eq!(1663214400, entry.time.timestamp());
+        assert_eq!(Some(9.4), entry.report.temperature_min);
+        assert_eq!(Some(17.2), entry.report.temperature_max);
+        assert_eq!(Some(5.0), entry.report.wind_speed);
+        assert_eq!(Some(270), entry.report.wind_direction);
+        assert_eq!(Some(1.5), entry.report.precipitation);
+        assert_eq!(Some(61.0), entry.report.humidity);
+        assert_eq!(Some("Partly sunny".to_owned()), entry.report.description);
+    }
+
+    // Minimum temperature is the day of month. 1663268400, 2022-09-15 19:00 UTC,
+    // is on the 16th in UTC+12 and on the 15th in UTC-12.
+    static FAR_EAST_DAYS: &str = r#"{"DailyForecasts": [
+        {"Date": "2022-09-15T07:00:00+12:00", "EpochDate": 1663182000, "Temperature": {"Minimum": {"Value": 15, "Unit": "C"}, "Maximum": {"Value": 20, "Unit": "C"}}},
+        {"Date": "2022-09-16T07:00:00+12:00", "EpochDate": 1663268400, "Temperature": {"Minimum": {"Value": 16, "Unit": "C"}, "Maximum": {"Value": 20, "Unit": "C"}}}
+    ]}"#;
+    static FAR_WEST_DAYS: &str = r#"{"DailyForecasts": [
+        {"Date": "2022-09-15T07:00:00-12:00", "EpochDate": 1663268400, "Temperature": {"Minimum": {"Value": 15, "Unit": "C"}, "Maximum": {"Value": 20, "Unit": "C"}}},
+        {"Date": "2022-09-16T07:00:00-12:00", "EpochDate": 1663354800, "Temperature": {"Minimum": {"Value": 16, "Unit": "C"}, "Maximum": {"Value": 20, "Unit": "C"}}}
+    ]}"#;
+
+    #[test]
+    fn forecast_day_taken_in_location_time() {
+        let date = NaiveDate::from_ymd_opt(2022, 9, 16).unwrap();
+        for json in [FAR_EAST_DAYS, FAR_WEST_DAYS] {
+            let forecasts: DailyForecasts = serde_json::from_str(json).unwrap();
+            let offset = forecasts.offset();
+            let day = forecasts.into_forecast(2).into_day(offset, date).unwrap();
+            assert_eq!(Some(16.0), day.report.temperature_min);
+        }
+    }
+
+    #[test]
+    fn forecast_days_out_of_range_error_expected() {
+        let provider = AccuweatherProvider::new("testprovider", Some("somekey"));
+        assert_eq!(
+            Err(WeatherError::ForecastDaysError(15)),
+            provider.get_forecast(&lviv(), 16)
+        );
+        assert_eq!(
+            Err(WeatherError::ForecastDaysError(15)),
+            provider.get_forecast(&lviv(), 0)
+        );
+    }
+
+    #[test]
+    fn historical_report_for_date_ok() {
+        let observations: Vec<CurrentConditions> = serde_json::from_str(
+            r#"[
+                {"LocalObservationDateTime": "2022-09-15T00:00:00+03:00", "WeatherText": "Clear", "Temperature": {"Metric": {"Value": 9.0, "Unit": "C"}}},
+                {"LocalObservationDateTime": "2022-09-14T23:00:00+03:00", "WeatherText": "Clear", "Temperature": {"Metric": {"Value": 10.0, "Unit": "C"}}},
+                {"LocalObservationDateTime": "2022-09-14T13:00:00+03:00", "WeatherText": "Sunny", "Temperature": {"Metric": {"Value": 18.5, "Unit": "C"}}},
+                {"LocalObservationDateTime": "2022-09-14T06:00:00+03:00", "WeatherText": "Fog", "Temperature": {"Metric": {"Value": 7.5, "Unit": "C"}}}
+            ]"#,
+        )
+        .unwrap();
+        let date = NaiveDate::from_ymd_opt(2022, 9, 14).unwrap();
+        let report = historical_report(&observations, date).unwrap();
+        assert_eq!(Some(18.5), report.temperature);
+        assert_eq!(Some(7.5), report.temperature_min);
+        assert_eq!(Some(18.5), report.temperature_max);
+        assert_eq!(Some("Sunny".to_owned()), report.description);
+
+        let date = NaiveDate::from_ymd_opt(2022, 9, 13).unwrap();
+        assert_eq!(None, historical_report(&observations, date));
+    }
+
+    #[test]
+    fn unsupported_dates_error_expected() {
+        let provider = AccuweatherProvider::new("testprovider", Some("somekey"));
+        let today = Local::now().date_naive();
+        for date in [today - Duration::days(2), today + Duration::days(15)] {
+            assert_eq!(
+                Err(WeatherError::DateNotSupportedError(date)),
+                provider.get_report(&lviv(), date)
+            );
+        }
+    }
 
     #[test]
     fn no_api_key_error_expected() {
         let provider = AccuweatherProvider::new("testprovider", None);
-        let result = provider.get_report("foo", Local::now().date_naive());
+        let result = provider.get_report(&lviv(), Local::now().date_naive());
         assert_eq!(Err(WeatherError::NoApiKeyError), result);
     }
 
     #[test]
     fn some_invalid_api_key_error_expected() {
-        let provider = AccuweatherProvider::new("testprovider", Some("somekey"));
-        let result = provider.get_report("foo", Local::now().date_naive());
-        assert_eq!(Err(WeatherError::HttpError("{\"Code\":\"Unauthorized\",\"Message\":\"Api Authorization failed\",\"Reference\":\"/locations/v1/cities/autocomplete?apikey=somekey&q=foo\"}".to_owned())), result);
+        let result = replaying("invalid_api_key").get_report(&lviv(), Local::now().date_naive());
+        match result {
+            Err(WeatherError::HttpError(body)) => {
+                assert!(body.contains("Api Authorization failed"))
+            }
+            other => panic!("Unexpected result {other:?}"),
+        }
+    }
+
+    #[test]
+    fn recorded_credentials_ok() {
+        let status = replaying("doctor").validate_credentials().unwrap();
+        assert_eq!(Some(48), status.quota_remaining);
+
+        match replaying("invalid_api_key").validate_credentials() {
+            Err(WeatherError::HttpError(body)) => assert!(body.contains("Unauthorized")),
+            other => panic!("Unexpected result {other:?}"),
+        }
+    }
+
+    #[test]
+    fn env_api_key_never_saved() {
+        let mut provider = AccuweatherProvider::new("Env Accuweather", Some("configured"));
+        assert_eq!(
+            "WEATHER_ENV_ACCUWEATHER_API_KEY",
+            api_key_env_var(&provider.get_name())
+        );
+        let from_env = Some("from-env".to_owned());
+        assert_eq!(
+            from_env,
+            api_key_in_use(from_env.clone(), provider.get_saved_api_key())
+        );
+
+        let saved = serde_json::to_string(&provider).unwrap();
+        assert!(saved.contains("configured"));
+        assert!(!saved.contains("from-env"));
+
+        provider.api_key = None;
+        assert_eq!(
+            from_env,
+            api_key_in_use(from_env.clone(), provider.get_saved_api_key())
+        );
+        assert_eq!(None, api_key_in_use(None, provider.get_saved_api_key()));
     }
 }
@@ -1,2 +1,3 @@
 pub mod openweather_api;
-pub mod accuweather_api;
\ No newline at end of file
+pub mod accuweather_api;
+pub mod openmeteo_api;
\ No newline at end of file
@@ -0,0 +1,596 @@
+use chrono::{Duration, FixedOffset, Local, NaiveDate, TimeZone, Timelike, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    api::{
+        geocoder_api::{Geocoder, Location},
+        provider_api::{
+            ApiStatus, ForecastEntry, WeatherForecast, WeatherProvider, WeatherReport,
+            PROBE_LOCATION,
+        },
+    },
+    error::WeatherError,
+    geocoders::openmeteo_geocoder::OpenMeteoGeocoder,
+    http_client::{url_with_params, HttpClient},
+    secrets,
+    units::UnitSystem,
+};
+
+/// Longest forecast Open-Meteo provides.
+const MAX_FORECAST_DAYS: u8 = 16;
+
+/// How far in the past forecast API still has data. Older dates are looked up in archive.
+const MAX_PAST_DAYS: i64 = 92;
+
+const CURRENT_FIELDS: &str = "temperature_2m,apparent_temperature,relative_humidity_2m,pressure_msl,wind_speed_10m,wind_direction_10m,cloud_cover,precipitation,weather_code";
+const HOURLY_FIELDS: &str = CURRENT_FIELDS;
+const DAILY_FIELDS: &str = "weather_code,temperature_2m_max,temperature_2m_min,precipitation_sum,wind_speed_10m_max,wind_direction_10m_dominant";
+
+fn default_geocoding_url() -> String {
+    "https://geocoding-api.open-meteo.com".to_owned()
+}
+
+fn default_forecast_url() -> String {
+    "https://api.open-meteo.com".to_owned()
+}
+
+fn default_archive_url() -> String {
+    "https://archive-api.open-meteo.com".to_owned()
+}
+
+/// [`WeatherProvider`] implementation for [`OpenMeteoProvider`].
+/// Needs no API_KEY, the one of commercial plan is passed along if set.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OpenMeteoProvider {
+    /// Using sync client since we are all sync. No need to store as well.
+    #[serde(skip)]
+    client: HttpClient,
+    provider_name: String,
+    api_key: Option<String>,
+    #[serde(default = "default_geocoding_url")]
+    geocoding_url: String,
+    #[serde(default = "default_forecast_url")]
+    forecast_url: String,
+    #[serde(default = "default_archive_url")]
+    archive_url: String,
+}
+
+/// Current conditions, times are unix timestamps.
+#[derive(Debug, Serialize, Deserialize)]
+struct Current {
+    time: i64,
+    temperature_2m: Option<f32>,
+    apparent_temperature: Option<f32>,
+    relative_humidity_2m: Option<f32>,
+    pressure_msl: Option<f32>,
+    wind_speed_10m: Option<f32>,
+    wind_direction_10m: Option<f32>,
+    cloud_cover: Option<f32>,
+    precipitation: Option<f32>,
+    weather_code: Option<u32>,
+}
+
+/// Hourly series, every vector has an item per `time`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Hourly {
+    time: Vec<i64>,
+    temperature_2m: Vec<Option<f32>>,
+    apparent_temperature: Vec<Option<f32>>,
+    relative_humidity_2m: Vec<Option<f32>>,
+    pressure_msl: Vec<Option<f32>>,
+    wind_speed_10m: Vec<Option<f32>>,
+    wind_direction_10m: Vec<Option<f32>>,
+    cloud_cover: Vec<Option<f32>>,
+    precipitation: Vec<Option<f32>>,
+    weather_code: Vec<Option<u32>>,
+}
+
+/// Daily series, every vector has an item per `time`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Daily {
+    time: Vec<i64>,
+    weather_code: Vec<Option<u32>>,
+    temperature_2m_max: Vec<Option<f32>>,
+    temperature_2m_min: Vec<Option<f32>>,
+    precipitation_sum: Vec<Option<f32>>,
+    wind_speed_10m_max: Vec<Option<f32>>,
+    wind_direction_10m_dominant: Vec<Option<f32>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Response {
+    current: Option<Current>,
+    hourly: Option<Hourly>,
+    daily: Option<Daily>,
+    #[serde(default)]
+    utc_offset_seconds: i32,
+}
+
+/// Takes `index` item of a series if there is one.
+fn at<T: Copy>(series: &[Option<T>], index: usize) -> Option<T> {
+    series.get(index).copied().flatten()
+}
+
+/// Describes WMO weather interpretation code Open-Meteo uses as a condition code.
+fn describe(code: u32) -> Option<String> {
+    let description = match code {
+        0 => "clear sky",
+        1 => "mainly clear",
+        2 => "partly cloudy",
+        3 => "overcast",
+        45 | 48 => "fog",
+        51 | 53 | 55 => "drizzle",
+        56 | 57 => "freezing drizzle",
+        61 | 63 | 65 => "rain",
+        66 | 67 => "freezing rain",
+        71 | 73 | 75 => "snow fall",
+        77 => "snow grains",
+        80..=82 => "rain showers",
+        85 | 86 => "snow showers",
+        95 => "thunderstorm",
+        96 | 99 => "thunderstorm with hail",
+        _ => return None,
+    };
+    Some(description.to_owned())
+}
+
+impl From<Current> for WeatherReport {
+    fn from(current: Current) -> Self {
+        WeatherReport {
+            units: UnitSystem::Metric,
+            temperature: current.temperature_2m,
+            feels_like: current.apparent_temperature,
+            humidity: current.relative_humidity_2m,
+            pressure: current.pressure_msl,
+            wind_speed: current.wind_speed_10m,
+            wind_direction: current.wind_direction_10m.map(|d| d.round() as u16),
+            cloud_cover: current.cloud_cover,
+            precipitation: current.precipitation,
+            condition_code: current.weather_code,
+            description: current.weather_code.and_then(describe),
+            observed_at: Utc.timestamp_opt(current.time, 0).single(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Hourly {
+    fn entries(&self) -> Vec<ForecastEntry> {
+        self.time
+            .iter()
+            .enumerate()
+            .filter_map(|(i, time)| {
+                let code = at(&self.weather_code, i);
+                Some(ForecastEntry {
+                    time: Utc.timestamp_opt(*time, 0).single()?,
+                    report: WeatherReport {
+                        units: UnitSystem::Metric,
+                        temperature: at(&self.temperature_2m, i),
+                        feels_like: at(&self.apparent_temperature, i),
+                        humidity: at(&self.relative_humidity_2m, i),
+                        pressure: at(&self.pressure_msl, i),
+                        wind_speed: at(&self.wind_speed_10m, i),
+                        wind_direction: at(&self.wind_direction_10m, i).map(|d| d.round() as u16),
+                        cloud_cover: at(&self.cloud_cover, i),
+                        precipitation: at(&self.precipitation, i),
+                        condition_code: code,
+                        description: code.and_then(describe),
+                        ..Default::default()
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
+impl Daily {
+    fn entries(&self) -> Vec<ForecastEntry> {
+        self.time
+            .iter()
+            .enumerate()
+            .filter_map(|(i, time)| {
+                let code = at(&self.weather_code, i);
+                Some(ForecastEntry {
+                    time: Utc.timestamp_opt(*time, 0).single()?,
+                    report: WeatherReport {
+                        units: UnitSystem::Metric,
+                        temperature_min: at(&self.temperature_2m_min, i),
+                        temperature_max: at(&self.temperature_2m_max, i),
+                        wind_speed: at(&self.wind_speed_10m_max, i),
+                        wind_direction: at(&self.wind_direction_10m_dominant, i)
+                            .map(|d| d.round() as u16),
+                        precipitation: at(&self.precipitation_sum, i),
+                        condition_code: code,
+                        description: code.and_then(describe),
+                        ..Default::default()
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
+impl Response {
+    fn into_forecast(self) -> WeatherForecast {
+        WeatherForecast {
+            daily: self.daily.map(|d| d.entries()).unwrap_or_default(),
+            hourly: self.hourly.map(|h| h.entries()).unwrap_or_default(),
+            location: None,
+            offset: FixedOffset::east_opt(self.utc_offset_seconds),
+        }
+    }
+
+    /// Builds a report for a single day response: daily values completed with the ones of local midday.
+    fn into_day_report(self) -> Option<WeatherReport> {
+        let offset = Duration::seconds(self.utc_offset_seconds as i64);
+        let forecast = self.into_forecast();
+        let day = forecast.daily.into_iter().next()?.report;
+        let midday = forecast
+            .hourly
+            .into_iter()
+            .min_by_key(|e| ((e.time + offset).hour() as i32 - 12).abs())
+            .map(|e| e.report)
+            .unwrap_or_default();
+        Some(WeatherReport {
+            temperature: midday.temperature,
+            feels_like: midday.feels_like,
+            humidity: midday.humidity,
+            pressure: midday.pressure,
+            cloud_cover: midday.cloud_cover,
+            observed_at: None,
+            ..day
+        })
+    }
+}
+
+impl OpenMeteoProvider {
+    pub fn new(provider_name: &str, api_key: Option<&str>) -> Self {
+        OpenMeteoProvider {
+            client: HttpClient::default(),
+            provider_name: provider_name.to_owned(),
+            api_key: api_key.map(str::to_string),
+            geocoding_url: default_geocoding_url(),
+            forecast_url: default_forecast_url(),
+            archive_url: default_archive_url(),
+        }
+    }
+
+    /// Executes GET request of the endpoint passing API_KEY along if there is one.
+    /// Parameters common to every request follow the given ones.
+    fn fetch<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        location: &Location,
+        params: &[(&str, &str)],
+    ) -> Result<T, WeatherError> {
+        let (lat, lon) = (location.lat.to_string(), location.lon.to_string());
+        let mut query = vec![("latitude", lat.as_str()), ("longitude", lon.as_str())];
+        query.extend_from_slice(params);
+        query.extend([
+            ("wind_speed_unit", "ms"),
+            ("timeformat", "unixtime"),
+            ("timezone", "auto"),
+        ]);
+        let api_key = self.get_api_key();
+        if let Some(api_key) = &api_key {
+            query.push(("apikey", api_key));
+        }
+        self.client.get_json(&url_with_params(endpoint, &query)?)
+    }
+
+    fn get_current(&self, location: &Location) -> Result<WeatherReport, WeatherError> {
+        let response: Response = self.fetch(
+            &format!("{}/v1/forecast", self.forecast_url),
+            location,
+            &[("current", CURRENT_FIELDS)],
+        )?;
+        response
+            .current
+            .map(WeatherReport::from)
+            .ok_or(WeatherError::NoReportFoundError)
+    }
+
+    /// Recent past and future dates are served by forecast API, older ones by archive.
+    fn get_day(&self, location: &Location, date: NaiveDate) -> Result<WeatherReport, WeatherError> {
+        let today = Local::now().date_naive();
+        let base_url = if date < today - Duration::days(MAX_PAST_DAYS) {
+            format!("{}/v1/archive", self.archive_url)
+        } else {
+            format!("{}/v1/forecast", self.forecast_url)
+        };
+        let date_param = date.to_string();
+        let response: Response = self.fetch(
+            &base_url,
+            location,
+            &[
+                ("start_date", &date_param),
+                ("end_date", &date_param),
+                ("daily", DAILY_FIELDS),
+                ("hourly", HOURLY_FIELDS),
+            ],
+        )?;
+        response
+            .into_day_report()
+            .ok_or(WeatherError::DateNotSupportedError(date))
+    }
+}
+
+/// Archive starts from 1940-01-01.
+fn history_start() -> NaiveDate {
+    NaiveDate::from_ymd_opt(1940, 1, 1).unwrap()
+}
+
+#[typetag::serde]
+impl WeatherProvider for OpenMeteoProvider {
+    fn get_name(&self) -> String {
+        self.provider_name.clone()
+    }
+
+    fn get_saved_api_key(&self) -> Option<String> {
+        self.api_key.clone()
+    }
+
+    /// The key of commercial plan is optional.
+    fn requires_api_key(&self) -> bool {
+        false
+    }
+
+    fn set_api_key(&mut self, api_key: &str) -> Result<(), WeatherError> {
+        self.api_key = Some(secrets::conceal(api_key)?);
+        Ok(())
+    }
+
+    fn get_base_url(&self) -> String {
+        self.forecast_url.clone()
+    }
+
+    /// Open-Meteo serves geocoding, forecast and archive from different hosts, the base URL
+    /// replaces all of them since their paths differ. Resetting restores each one.
+    fn set_base_url(&mut self, base_url: Option<&str>) {
+        match base_url {
+            Some(base_url) => {
+                self.geocoding_url = base_url.to_owned();
+                self.forecast_url = base_url.to_owned();
+                self.archive_url = base_url.to_owned();
+            }
+            None => {
+                self.geocoding_url = default_geocoding_url();
+                self.forecast_url = default_forecast_url();
+                self.archive_url = default_archive_url();
+            }
+        }
+    }
+
+    /// Needs no API_KEY, the request checks the commercial one if set and that the API is reachable.
+    fn validate_credentials(&self) -> Result<ApiStatus, WeatherError> {
+        let (lat, lon) = PROBE_LOCATION;
+        self.get_current(&Location {
+            lat,
+            lon,
+            ..Default::default()
+        })?;
+        Ok(ApiStatus::default())
+    }
+
+    fn get_geocoder(&self) -> Box<dyn Geocoder> {
+        Box::new(OpenMeteoGeocoder::new(
+            self.client.clone(),
+            &self.geocoding_url,
+            self.get_api_key().as_deref(),
+        ))
+    }
+
+    fn get_report(
+        &self,
+        location: &Location,
+        date: NaiveDate,
+    ) -> Result<WeatherReport, WeatherError> {
+        let today = Local::now().date_naive();
+        if date == today {
+            return self.get_current(location);
+        }
+        if date < history_start() || (date - today).num_days() >= MAX_FORECAST_DAYS as i64 {
+            return Err(WeatherError::DateNotSupportedError(date));
+        }
+        self.get_day(location, date)
+    }
+
+    fn get_forecast(&self, location: &Location, days: u8) -> Result<WeatherForecast, WeatherError> {
+        if !(1..=MAX_FORECAST_DAYS).contains(&days) {
+            return Err(WeatherError::ForecastDaysError(MAX_FORECAST_DAYS));
+        }
+        let response: Response = self.fetch(
+            &format!("{}/v1/forecast", self.forecast_url),
+            location,
+            &[
+                ("forecast_days", &days.to_string()),
+                ("daily", DAILY_FIELDS),
+                ("hourly", HOURLY_FIELDS),
+            ],
+        )?;
+        Ok(response.into_forecast())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Local, NaiveDate};
+
+    use crate::{
+        api::{geocoder_api::Location, provider_api::WeatherProvider},
+        error::WeatherError,
+        http_client::HttpClient,
+        mock_server::MockServer,
+    };
+
+    use super::OpenMeteoProvider;
+
+    fn test_provider(server: &MockServer) -> OpenMeteoProvider {
+        let mut provider = OpenMeteoProvider::new("testprovider", None);
+        provider.set_base_url(Some(&server.url()));
+        provider
+    }
+
+    fn lviv() -> Location {
+        Location {
+            name: "Lviv".to_owned(),
+            lat: 49.83826,
+            lon: 24.02324,
+            ..Default::default()
+        }
+    }
+
+    static CURRENT: &str = r#"{
+        "latitude": 49.84, "longitude": 24.02, "utc_offset_seconds": 10800,
+        "current": {
+            "time": 1663236000, "interval": 900, "temperature_2m": 15.2, "apparent_temperature": 14.1,
+            "relative_humidity_2m": 72, "pressure_msl": 1013.4, "wind_speed_10m": 3.4, "wind_direction_10m": 251,
+            "cloud_cover": 40, "precipitation": 0.0, "weather_code": 2
+        }
+    }"#;
+
+    // Local midday of 2022-09-16 in UTC+3 is 1663318800.
+    static DAY: &str = r#"{
+        "latitude": 49.84, "longitude": 24.02, "utc_offset_seconds": 10800,
+        "hourly": {
+            "time": [1663297200, 1663318800, 1663340400],
+            "temperature_2m": [9.1, 17.3, 12.0],
+            "apparent_temperature": [8.0, 16.9, 11.2],
+            "relative_humidity_2m": [90, 55, 70],
+            "pressure_msl": [1015.0, 1014.2, 1013.0],
+            "cloud_cover": [100, 20, 60],
+            "weather_code": [3, 1, 61]
+        },
+        "daily": {
+            "time": [1663275600],
+            "weather_code": [61],
+            "temperature_2m_max": [18.2],
+            "temperature_2m_min": [8.7],
+            "precipitation_sum": [2.4],
+            "wind_speed_10m_max": [5.5],
+            "wind_direction_10m_dominant": [260]
+        }
+    }"#;
+
+    #[test]
+    fn current_report_ok() {
+        let server = MockServer::start(&[("/v1/forecast", 200, CURRENT)]);
+        let provider = test_provider(&server);
+
+        let report = provider
+            .get_report(&lviv(), Local::now().date_naive())
+            .unwrap();
+        assert_eq!(Some(15.2), report.temperature);
+        assert_eq!(Some(14.1), report.feels_like);
+        assert_eq!(Some(251), report.wind_direction);
+        assert_eq!(Some("partly cloudy".to_owned()), report.description);
+
+        assert!(server.requests()[0].contains("latitude=49.83826&longitude=24.02324"));
+    }
+
+    #[test]
+    fn api_key_encoded_ok() {
+        let server = MockServer::start(&[("/v1/forecast", 200, CURRENT)]);
+        let mut provider = OpenMeteoProvider::new("Encoded Open-Meteo", Some("a&b#c"));
+        provider.set_base_url(Some(&server.url()));
+
+        provider
+            .get_report(&lviv(), Local::now().date_naive())
+            .unwrap();
+        assert!(server.requests()[0].ends_with("&apikey=a%26b%23c"));
+    }
+
+    #[test]
+    fn day_report_ok() {
+        let server = MockServer::start(&[("/v1/forecast", 200, DAY)]);
+        let provider = test_provider(&server);
+
+        let date = Local::now().date_naive() + Duration::days(3);
+        let report = provider.get_report(&lviv(), date).unwrap();
+        assert_eq!(Some(17.3), report.temperature);
+        assert_eq!(Some(8.7), report.temperature_min);
+        assert_eq!(Some(18.2), report.temperature_max);
+        assert_eq!(Some(55.0), report.humidity);
+        assert_eq!(Some(2.4), report.precipitation);
+        assert_eq!(Some("rain".to_owned()), report.description);
+        assert!(server.requests()[0].contains(&format!("start_date={date}&end_date={date}")));
+    }
+
+    #[test]
+    fn old_dates_go_to_archive() {
+        let server = MockServer::start(&[("/v1/archive", 200, DAY)]);
+        let provider = test_provider(&server);
+
+        let date = Local::now().date_naive() - Duration::days(365);
+        assert!(provider.get_report(&lviv(), date).is_ok());
+        assert!(server.requests()[0].starts_with("/v1/archive?"));
+    }
+
+    /// Provider serving responses recorded into `fixtures/openmeteo/<scenario>`.
+    fn replaying(scenario: &str) -> OpenMeteoProvider {
+        let mut provider = OpenMeteoProvider::new("testprovider", None);
+        provider.client = HttpClient::replaying(&format!("openmeteo/{scenario}"));
+        provider
+    }
+
+    #[test]
+    fn recorded_current_report_ok() {
+        let report = replaying("current")
+            .get_report(&lviv(), Local::now().date_naive())
+            .unwrap();
+        assert_eq!(Some(15.2), report.temperature);
+        assert_eq!(Some(1013.4), report.pressure);
+        assert_eq!(Some("partly cloudy".to_owned()), report.description);
+    }
+
+    #[test]
+    fn recorded_archive_report_ok() {
+        let report = replaying("archive")
+            .get_report(&lviv(), NaiveDate::from_ymd_opt(2022, 9, 14).unwrap())
+            .unwrap();
+        assert_eq!(Some(16.8), report.temperature);
+        assert_eq!(Some(9.6), report.temperature_min);
+        assert_eq!(Some(18.0), report.temperature_max);
+        assert_eq!(Some("mainly clear".to_owned()), report.description);
+    }
+
+    #[test]
+    fn recorded_forecast_ok() {
+        let forecast = replaying("forecast").get_forecast(&lviv(), 3).unwrap();
+        assert_eq!(3, forecast.daily.len());
+        assert_eq!(72, forecast.hourly.len());
+        assert_eq!(Some(2.4), forecast.daily[1].report.precipitation);
+        assert_eq!(
+            Some("rain".to_owned()),
+            forecast.daily[1].report.description
+        );
+    }
+
+    #[test]
+    fn recorded_invalid_request_error_expected() {
+        let result = replaying("invalid_request").get_forecast(&lviv(), 3);
+        match result {
+            Err(WeatherError::HttpError(body)) => {
+                assert!(body.contains("Cannot initialize WeatherVariable"))
+            }
+            other => panic!("Unexpected result {other:?}"),
+        }
+    }
+
+    #[test]
+    fn unsupported_dates_error_expected() {
+        let provider = OpenMeteoProvider::new("testprovider", None);
+        let date = Local::now().date_naive() + Duration::days(16);
+        assert_eq!(
+            Err(WeatherError::DateNotSupportedError(date)),
+            provider.get_report(&lviv(), date)
+        );
+    }
+
+    #[test]
+    fn recorded_credentials_ok() {
+        assert!(replaying("doctor").validate_credentials().is_ok());
+    }
+}
@@ -1,50 +1,330 @@
-use std::fmt::Debug;
+use std::{cmp::Ordering, fmt::Debug};
 
-use chrono::NaiveDate;
-use reqwest::{blocking::Client, StatusCode};
+use chrono::{FixedOffset, Local, NaiveDate, TimeZone, Timelike, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    api::provider_api::{WeatherProvider, WeatherReport},
+    api::{
+        geocoder_api::{Geocoder, Location},
+        provider_api::{
+            ApiStatus, ForecastEntry, WeatherForecast, WeatherProvider, WeatherReport,
+            PROBE_LOCATION,
+        },
+    },
     error::WeatherError,
+    geocoders::openweather_geocoder::OpenWeatherGeocoder,
+    http_client::HttpClient,
+    secrets,
+    units::UnitSystem,
 };
 
+/// 5 day / 3 hour forecast is the longest one available for free.
+const MAX_FORECAST_DAYS: u8 = 5;
+
 /// [`WeatherProvider`] implementation for [`OpenWeatherProvider`].
-/// Naïve and no historical data support.
+/// History is looked up with One Call API which needs a separate subscription.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct OpenWeatherProvider {
-    /// Using sync implementation of reqwest since we are all sync. No need to store as well.
+    /// Using sync client since we are all sync. No need to store as well.
     #[serde(skip)]
-    client: Client,
+    client: HttpClient,
     provider_name: String,
     api_key: Option<String>,
+    #[serde(default = "default_base_url")]
+    base_url: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Location {
-    name: String,
-    lat: f32,
-    lon: f32,
+fn default_base_url() -> String {
+    "https://api.openweathermap.org".to_owned()
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Main {
     temp: f32,
+    feels_like: Option<f32>,
+    temp_min: Option<f32>,
+    temp_max: Option<f32>,
+    pressure: Option<f32>,
+    humidity: Option<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Condition {
+    id: u32,
+    description: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Wind {
+    speed: Option<f32>,
+    deg: Option<u16>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Clouds {
+    all: Option<f32>,
+}
+
+/// Rain or snow volume. Current weather has the last hour one, forecast has 3 hours one.
+#[derive(Debug, Serialize, Deserialize)]
+struct Volume {
+    #[serde(rename = "1h")]
+    last_hour: Option<f32>,
+    #[serde(rename = "3h")]
+    last_3_hours: Option<f32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Report {
     main: Main,
+    #[serde(default)]
+    weather: Vec<Condition>,
+    wind: Option<Wind>,
+    clouds: Option<Clouds>,
+    rain: Option<Volume>,
+    snow: Option<Volume>,
+    dt: Option<i64>,
+}
+
+impl From<Report> for WeatherReport {
+    fn from(report: Report) -> Self {
+        let precipitation = [&report.rain, &report.snow]
+            .iter()
+            .filter_map(|volume| volume.as_ref().and_then(|v| v.last_hour.or(v.last_3_hours)))
+            .reduce(|a, b| a + b);
+        let condition = report.weather.first();
+        WeatherReport {
+            units: UnitSystem::Metric,
+            temperature: Some(report.main.temp),
+            feels_like: report.main.feels_like,
+            temperature_min: None,
+            temperature_max: None,
+            humidity: report.main.humidity,
+            pressure: report.main.pressure,
+            wind_speed: report.wind.as_ref().and_then(|wind| wind.speed),
+            wind_direction: report.wind.as_ref().and_then(|wind| wind.deg),
+            cloud_cover: report.clouds.and_then(|clouds| clouds.all),
+            precipitation,
+            condition_code: condition.map(|c| c.id),
+            description: condition.map(|c| c.description.clone()),
+            observed_at: report.dt.and_then(|dt| Utc.timestamp_opt(dt, 0).single()),
+            location: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct City {
+    /// Shift in seconds from UTC.
+    timezone: Option<i32>,
+}
+
+/// Response of 5 day / 3 hour forecast.
+#[derive(Debug, Serialize, Deserialize)]
+struct Forecast {
+    list: Vec<Report>,
+    city: Option<City>,
+}
+
+impl Forecast {
+    /// Shift of the city's time from UTC, UTC itself if it is unknown.
+    fn offset(&self) -> FixedOffset {
+        self.city
+            .as_ref()
+            .and_then(|city| city.timezone)
+            .and_then(FixedOffset::east_opt)
+            .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap())
+    }
+
+    /// Turns 3 hours entries into hourly ones and aggregates them per local day.
+    fn into_forecast(self, days: u8) -> WeatherForecast {
+        let offset = self.offset();
+
+        let hourly: Vec<ForecastEntry> = self
+            .list
+            .into_iter()
+            .filter_map(|report| {
+                let time = Utc.timestamp_opt(report.dt?, 0).single()?;
+                let (temperature_min, temperature_max) =
+                    (report.main.temp_min, report.main.temp_max);
+                Some(ForecastEntry {
+                    time,
+                    report: WeatherReport {
+                        temperature_min,
+                        temperature_max,
+                        observed_at: None,
+                        ..report.into()
+                    },
+                })
+            })
+            .collect();
+
+        let mut grouped: Vec<(NaiveDate, Vec<&ForecastEntry>)> = vec![];
+        for entry in &hourly {
+            let date = entry.time.with_timezone(&offset).date_naive();
+            if let Some((last, entries)) = grouped.last_mut() {
+                if *last == date {
+                    entries.push(entry);
+                    continue;
+                }
+            }
+            if grouped.len() == days as usize {
+                break;
+            }
+            grouped.push((date, vec![entry]));
+        }
+
+        let daily = grouped
+            .iter()
+            .map(|(date, entries)| {
+                // Conditions are taken from the entry closest to the local midday.
+                let midday = entries
+                    .iter()
+                    .min_by_key(|e| (e.time.with_timezone(&offset).hour() as i32 - 12).abs())
+                    .unwrap();
+                ForecastEntry {
+                    time: offset
+                        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+                        .unwrap()
+                        .with_timezone(&Utc),
+                    report: WeatherReport {
+                        temperature: None,
+                        feels_like: None,
+                        temperature_min: entries
+                            .iter()
+                            .filter_map(|e| e.report.temperature_min.or(e.report.temperature))
+                            .reduce(f32::min),
+                        temperature_max: entries
+                            .iter()
+                            .filter_map(|e| e.report.temperature_max.or(e.report.temperature))
+                            .reduce(f32::max),
+                        precipitation: entries
+                            .iter()
+                            .filter_map(|e| e.report.precipitation)
+                            .reduce(|a, b| a + b),
+                        ..midday.report.clone()
+                    },
+                }
+            })
+            .collect();
+
+        let last_time = grouped
+            .last()
+            .and_then(|(_, entries)| entries.last())
+            .map(|e| e.time);
+        WeatherForecast {
+            daily,
+            hourly: hourly
+                .into_iter()
+                .filter(|e| Some(e.time) <= last_time)
+                .collect(),
+            location: None,
+            offset: Some(offset),
+        }
+    }
+}
+
+/// Single entry of One Call API historical data.
+#[derive(Debug, Serialize, Deserialize)]
+struct HistoricalData {
+    dt: i64,
+    temp: f32,
+    feels_like: Option<f32>,
+    pressure: Option<f32>,
+    humidity: Option<f32>,
+    clouds: Option<f32>,
+    wind_speed: Option<f32>,
+    wind_deg: Option<u16>,
+    #[serde(default)]
+    weather: Vec<Condition>,
+    rain: Option<Volume>,
+    snow: Option<Volume>,
+}
+
+/// Response of One Call API time machine.
+#[derive(Debug, Serialize, Deserialize)]
+struct Historical {
+    data: Vec<HistoricalData>,
+}
+
+impl From<HistoricalData> for WeatherReport {
+    fn from(data: HistoricalData) -> Self {
+        Report {
+            main: Main {
+                temp: data.temp,
+                feels_like: data.feels_like,
+                temp_min: None,
+                temp_max: None,
+                pressure: data.pressure,
+                humidity: data.humidity,
+            },
+            weather: data.weather,
+            wind: Some(Wind {
+                speed: data.wind_speed,
+                deg: data.wind_deg,
+            }),
+            clouds: Some(Clouds { all: data.clouds }),
+            rain: data.rain,
+            snow: data.snow,
+            dt: Some(data.dt),
+        }
+        .into()
+    }
 }
 
 impl OpenWeatherProvider {
     pub fn new(provider_name: &str, api_key: Option<&str>) -> Self {
         OpenWeatherProvider {
-            client: Client::new(),
+            client: HttpClient::default(),
             provider_name: provider_name.to_owned(),
             api_key: api_key.map(str::to_string),
+            base_url: default_base_url(),
         }
     }
+
+    fn get_current(&self, location: &Location) -> Result<WeatherReport, WeatherError> {
+        let report: Report = self.client.get_json(&format!(
+            "{}/data/2.5/weather?units=metric&lat={}&lon={}&appid={}",
+            self.base_url,
+            location.lat,
+            location.lon,
+            self.get_api_key().ok_or(WeatherError::NoApiKeyError)?
+        ))?;
+        Ok(report.into())
+    }
+
+    /// Local midday is unknown before the lookup so the one of UTC is used.
+    fn get_historical(
+        &self,
+        location: &Location,
+        date: NaiveDate,
+    ) -> Result<WeatherReport, WeatherError> {
+        let midday = Utc.from_utc_datetime(&date.and_hms_opt(12, 0, 0).unwrap());
+        let historical: Historical = self.client.get_json(&format!(
+            "{}/data/3.0/onecall/timemachine?units=metric&lat={}&lon={}&dt={}&appid={}",
+            self.base_url,
+            location.lat,
+            location.lon,
+            midday.timestamp(),
+            self.get_api_key().ok_or(WeatherError::NoApiKeyError)?
+        ))?;
+        historical
+            .data
+            .into_iter()
+            .next()
+            .map(WeatherReport::from)
+            .ok_or(WeatherError::NoReportFoundError)
+    }
+
+    fn get_forecast_for(&self, location: &Location) -> Result<Forecast, WeatherError> {
+        self.client.get_json(&format!(
+            "{}/data/2.5/forecast?units=metric&lat={}&lon={}&appid={}",
+            self.base_url,
+            location.lat,
+            location.lon,
+            self.get_api_key().ok_or(WeatherError::NoApiKeyError)?
+        ))
+    }
 }
 
 #[typetag::serde]
@@ -53,45 +333,335 @@ impl WeatherProvider for OpenWeatherProvider {
         self.provider_name.clone()
     }
 
-    fn get_api_key(&self) -> Option<String> {
+    fn get_saved_api_key(&self) -> Option<String> {
         self.api_key.clone()
     }
 
-    fn set_api_key(&mut self, api_key: &str) {
-        self.api_key = Some(api_key.to_owned());
+    fn set_api_key(&mut self, api_key: &str) -> Result<(), WeatherError> {
+        self.api_key = Some(secrets::conceal(api_key)?);
+        Ok(())
+    }
+
+    fn get_base_url(&self) -> String {
+        self.base_url.clone()
     }
 
-    fn get_report(&self, address: &str, _date: NaiveDate) -> Result<WeatherReport, WeatherError> {
-        let response = self
-            .client
-            .get(format!(
-                "http://api.openweathermap.org/geo/1.0/direct?q={}&limit=1&appid={}",
-                address,
-                self.get_api_key().ok_or(WeatherError::NoApiKeyError)?
-            ))
-            .send()?;
-        if response.status() != StatusCode::OK {
-            return Err(WeatherError::HttpError(response.text()?));
+    fn set_base_url(&mut self, base_url: Option<&str>) {
+        self.base_url = base_url.map_or_else(default_base_url, str::to_owned);
+    }
+
+    /// Current weather is available to every plan, unlike history.
+    fn validate_credentials(&self) -> Result<ApiStatus, WeatherError> {
+        let (lat, lon) = PROBE_LOCATION;
+        self.get_current(&Location {
+            lat,
+            lon,
+            ..Default::default()
+        })?;
+        Ok(ApiStatus::default())
+    }
+
+    fn get_geocoder(&self) -> Box<dyn Geocoder> {
+        Box::new(OpenWeatherGeocoder::new(
+            self.client.clone(),
+            &self.base_url,
+            self.get_api_key().as_deref(),
+        ))
+    }
+
+    fn get_report(
+        &self,
+        location: &Location,
+        date: NaiveDate,
+    ) -> Result<WeatherReport, WeatherError> {
+        let today = Local::now().date_naive();
+        match date.cmp(&today) {
+            Ordering::Equal => self.get_current(location),
+            Ordering::Less if date >= history_start() => self.get_historical(location, date),
+            Ordering::Greater if (date - today).num_days() < MAX_FORECAST_DAYS as i64 => {
+                let forecast = self.get_forecast_for(location)?;
+                let offset = forecast.offset();
+                // Grouped in the city's time the entries may span a day more.
+                forecast
+                    .into_forecast(MAX_FORECAST_DAYS + 1)
+                    .into_day(offset, date)
+                    .map(|entry| entry.report)
+                    .ok_or(WeatherError::DateNotSupportedError(date))
+            }
+            _ => Err(WeatherError::DateNotSupportedError(date)),
         }
-        let locations: Vec<Location> = response.json()?;
-
-        if let Some(location) = locations.get(0) {
-            let response = self
-                .client
-                .get(format!(
-                    "https://api.openweathermap.org/data/2.5/weather?units=metric&lat={}&lon={}&appid={}",
-                    location.lat,
-                    location.lon,
-                    self.get_api_key().ok_or(WeatherError::NoApiKeyError)?
-                ))
-                .send()?;
-
-            let report: Report = response.json()?;
-            Ok(WeatherReport {
-                report: format!("{} C", report.main.temp),
-            })
-        } else {
-            Err(WeatherError::NoLocationFoundError)
+    }
+
+    fn get_forecast(&self, location: &Location, days: u8) -> Result<WeatherForecast, WeatherError> {
+        if !(1..=MAX_FORECAST_DAYS).contains(&days) {
+            return Err(WeatherError::ForecastDaysError(MAX_FORECAST_DAYS));
+        }
+        Ok(self.get_forecast_for(location)?.into_forecast(days))
+    }
+}
+
+/// One Call API history starts from 1979-01-01.
+fn history_start() -> NaiveDate {
+    NaiveDate::from_ymd_opt(1979, 1, 1).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, FixedOffset, Local, NaiveDate};
+
+    use super::{City, Forecast, Historical, OpenWeatherProvider, Report};
+    use crate::{
+        api::{
+            geocoder_api::Location,
+            provider_api::{WeatherProvider, WeatherReport},
+        },
+        error::WeatherError,
+        http_client::HttpClient,
+        mock_server::MockServer,
+    };
+
+    fn lviv() -> Location {
+        Location {
+            name: "Lviv".to_owned(),
+            lat: 49.8419,
+            lon: 24.0315,
+            ..Default::default()
+        }
+    }
+
+    fn test_provider(server: &MockServer) -> OpenWeatherProvider {
+        let mut provider = OpenWeatherProvider::new("testprovider", Some("somekey"));
+        provider.set_base_url(Some(&server.url()));
+        provider
+    }
+
+    static CURRENT_WEATHER: &str = r#"{
+        "weather": [{"id": 500, "main": "Rain", "description": "light rain", "icon": "10d"}],
+        "main": {"temp": 15.88, "feels_like": 15.4, "pressure": 1012, "humidity": 82},
+        "wind": {"speed": 4.12, "deg": 250},
+        "clouds": {"all": 75},
+        "rain": {"1h": 0.35},
+        "dt": 1663240000,
+        "name": "Lviv"
+    }"#;
+
+    #[test]
+    fn current_report_ok() {
+        let server = MockServer::start(&[("/data/2.5/weather", 200, CURRENT_WEATHER)]);
+        let provider = test_provider(&server);
+
+        let report = provider
+            .get_report(&lviv(), Local::now().date_naive())
+            .unwrap();
+        assert_eq!(Some(15.88), report.temperature);
+        assert_eq!(
+            vec!["/data/2.5/weather?units=metric&lat=49.8419&lon=24.0315&appid=somekey"],
+            server.requests()
+        );
+    }
+
+    /// Provider serving responses recorded into `fixtures/openweather/<scenario>`.
+    fn replaying(scenario: &str) -> OpenWeatherProvider {
+        let mut provider = OpenWeatherProvider::new("testprovider", Some("somekey"));
+        provider.client = HttpClient::replaying(&format!("openweather/{scenario}"));
+        provider
+    }
+
+    #[test]
+    fn recorded_current_report_ok() {
+        let report = replaying("current")
+            .get_report(&lviv(), Local::now().date_naive())
+            .unwrap();
+        assert_eq!(Some(15.88), report.temperature);
+        assert_eq!(Some("light rain".to_owned()), report.description);
+        assert_eq!(Some(0.35), report.precipitation);
+    }
+
+    #[test]
+    fn recorded_forecast_ok() {
+        let forecast = replaying("forecast").get_forecast(&lviv(), 2).unwrap();
+        assert_eq!(2, forecast.daily.len());
+        assert_eq!(10, forecast.hourly.len());
+
+        let first = &forecast.daily[0].report;
+        assert_eq!(Some(8.0), first.temperature_min);
+        assert_eq!(Some(14.0), first.temperature_max);
+        assert_eq!(Some(1.5), first.precipitation);
+        assert_eq!(Some("few clouds".to_owned()), first.description);
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2022, 9, 16).unwrap(),
+            forecast.daily[1]
+                .time
+                .with_timezone(&FixedOffset::east_opt(10800).unwrap())
+                .date_naive()
+        );
+    }
+
+    #[test]
+    fn recorded_historical_report_ok() {
+        let report = replaying("historical")
+            .get_report(&lviv(), NaiveDate::from_ymd_opt(2022, 9, 14).unwrap())
+            .unwrap();
+        assert_eq!(Some(18.3), report.temperature);
+        assert_eq!(Some("few clouds".to_owned()), report.description);
+        assert_eq!(1663156800, report.observed_at.unwrap().timestamp());
+    }
+
+    #[test]
+    fn recorded_invalid_api_key_error_expected() {
+        let result = replaying("invalid_api_key").get_report(&lviv(), Local::now().date_naive());
+        match result {
+            Err(WeatherError::HttpError(body)) => assert!(body.contains("Invalid API key")),
+            other => panic!("Unexpected result {other:?}"),
+        }
+    }
+
+    #[test]
+    fn report_fields_parsed_ok() {
+        let report: WeatherReport = serde_json::from_str::<Report>(CURRENT_WEATHER)
+            .unwrap()
+            .into();
+        assert_eq!(Some(15.88), report.temperature);
+        assert_eq!(Some(15.4), report.feels_like);
+        assert_eq!(Some(82.0), report.humidity);
+        assert_eq!(Some(1012.0), report.pressure);
+        assert_eq!(Some(4.12), report.wind_speed);
+        assert_eq!(Some(250), report.wind_direction);
+        assert_eq!(Some(75.0), report.cloud_cover);
+        assert_eq!(Some(0.35), report.precipitation);
+        assert_eq!(Some(500), report.condition_code);
+        assert_eq!(Some("light rain".to_owned()), report.description);
+        assert_eq!(1663240000, report.observed_at.unwrap().timestamp());
+    }
+
+    #[test]
+    fn missing_fields_are_none() {
+        let report: WeatherReport = serde_json::from_str::<Report>(r#"{"main": {"temp": 1.5}}"#)
+            .unwrap()
+            .into();
+        assert_eq!(
+            WeatherReport {
+                temperature: Some(1.5),
+                ..Default::default()
+            },
+            report
+        );
+    }
+
+    // 3 entries on 2022-09-15 and 2 on 2022-09-16 in local (UTC+3) time.
+    static FORECAST: &str = r#"{
+        "list": [
+            {"dt": 1663221600, "main": {"temp": 14.0, "temp_min": 13.5, "temp_max": 14.0}, "weather": [{"id": 800, "description": "clear sky"}]},
+            {"dt": 1663232400, "main": {"temp": 12.0, "temp_min": 12.0, "temp_max": 12.0}, "weather": [{"id": 801, "description": "few clouds"}], "rain": {"3h": 0.5}},
+            {"dt": 1663243200, "main": {"temp": 10.0, "temp_min": 9.5, "temp_max": 10.0}, "weather": [{"id": 500, "description": "light rain"}], "rain": {"3h": 1.0}},
+            {"dt": 1663318800, "main": {"temp": 16.0, "temp_min": 16.0, "temp_max": 16.5}, "weather": [{"id": 802, "description": "scattered clouds"}]},
+            {"dt": 1663329600, "main": {"temp": 18.0, "temp_min": 18.0, "temp_max": 18.0}, "weather": [{"id": 800, "description": "clear sky"}]}
+        ],
+        "city": {"name": "Lviv", "timezone": 10800}
+    }"#;
+
+    #[test]
+    fn forecast_aggregated_per_local_day() {
+        let forecast = serde_json::from_str::<Forecast>(FORECAST)
+            .unwrap()
+            .into_forecast(5);
+        assert_eq!(5, forecast.hourly.len());
+        assert_eq!(2, forecast.daily.len());
+
+        let first = &forecast.daily[0];
+        assert_eq!(
+            "2022-09-14T21:00:00Z",
+            first
+                .time
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        );
+        assert_eq!(Some(9.5), first.report.temperature_min);
+        assert_eq!(Some(14.0), first.report.temperature_max);
+        assert_eq!(Some(1.5), first.report.precipitation);
+        assert_eq!(Some("few clouds".to_owned()), first.report.description);
+
+        let second = &forecast.daily[1];
+        assert_eq!(Some(16.0), second.report.temperature_min);
+        assert_eq!(Some(18.0), second.report.temperature_max);
+        assert_eq!(None, second.report.precipitation);
+        assert_eq!(Some(802), second.report.condition_code);
+    }
+
+    #[test]
+    fn forecast_day_taken_in_city_time() {
+        let date = NaiveDate::from_ymd_opt(2022, 9, 16).unwrap();
+        for (timezone, temperature_range) in [(43200, (9.5, 16.5)), (-43200, (18.0, 18.0))] {
+            let mut forecast = serde_json::from_str::<Forecast>(FORECAST).unwrap();
+            forecast.city = Some(City {
+                timezone: Some(timezone),
+            });
+            let offset = forecast.offset();
+            let day = forecast.into_forecast(6).into_day(offset, date).unwrap();
+            assert_eq!(
+                temperature_range,
+                (
+                    day.report.temperature_min.unwrap(),
+                    day.report.temperature_max.unwrap()
+                )
+            );
         }
     }
+
+    #[test]
+    fn forecast_limited_to_requested_days() {
+        let forecast = serde_json::from_str::<Forecast>(FORECAST)
+            .unwrap()
+            .into_forecast(1);
+        assert_eq!(1, forecast.daily.len());
+        assert_eq!(3, forecast.hourly.len());
+    }
+
+    #[test]
+    fn historical_report_parsed_ok() {
+        let historical: Historical = serde_json::from_str(
+            r#"{
+                "lat": 49.84, "lon": 24.03, "timezone": "Europe/Kiev", "timezone_offset": 10800,
+                "data": [{
+                    "dt": 1663243200, "temp": 15.2, "feels_like": 14.8, "pressure": 1012, "humidity": 80,
+                    "clouds": 75, "wind_speed": 4.1, "wind_deg": 250,
+                    "weather": [{"id": 500, "main": "Rain", "description": "light rain", "icon": "10d"}],
+                    "rain": {"1h": 0.3}
+                }]
+            }"#,
+        )
+        .unwrap();
+        let report: WeatherReport = historical.data.into_iter().next().unwrap().into();
+        assert_eq!(Some(15.2), report.temperature);
+        assert_eq!(Some(4.1), report.wind_speed);
+        assert_eq!(Some(0.3), report.precipitation);
+        assert_eq!(1663243200, report.observed_at.unwrap().timestamp());
+    }
+
+    #[test]
+    fn unsupported_dates_error_expected() {
+        let provider = OpenWeatherProvider::new("testprovider", Some("somekey"));
+        let too_far = Local::now().date_naive() + Duration::days(5);
+        assert_eq!(
+            Err(WeatherError::DateNotSupportedError(too_far)),
+            provider.get_report(&lviv(), too_far)
+        );
+        let too_old = NaiveDate::from_ymd_opt(1978, 12, 31).unwrap();
+        assert_eq!(
+            Err(WeatherError::DateNotSupportedError(too_old)),
+            provider.get_report(&lviv(), too_old)
+        );
+    }
+
+    #[test]
+    fn recorded_credentials_ok() {
+        let status = replaying("doctor").validate_credentials().unwrap();
+        assert_eq!(None, status.quota_remaining);
+
+        let mut provider = replaying("doctor");
+        provider.api_key = None;
+        assert_eq!(
+            Err(WeatherError::NoApiKeyError),
+            provider.validate_credentials()
+        );
+    }
 }
@@ -0,0 +1,291 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::WeatherError, storage::paths};
+
+/// Marks API_KEY saved encrypted: `enc:v1:<base64 of nonce and ciphertext>`.
+/// Keys saved before they were encrypted have no mark and are taken as they are.
+const SEALED_PREFIX: &str = "enc:v1:";
+
+const NONCE_LEN: usize = 12;
+
+const SALT_LEN: usize = 16;
+
+/// Text encrypted with passphrase derived key to tell a wrong passphrase.
+const CHECK_TEXT: &str = "weather";
+
+/// Environment variable overriding the path of the key file.
+pub const KEY_FILE_VAR: &str = "WEATHER_KEY_FILE";
+
+/// Environment variable the passphrase is taken from instead of prompting for it, e.g. in scripts.
+pub const PASSPHRASE_VAR: &str = "WEATHER_PASSPHRASE";
+
+/// Cipher API keys are encrypted with while the app runs. Not set until [`unlock`] is called,
+/// encrypted keys can't be read then.
+static CIPHER: RwLock<Option<Cipher>> = RwLock::new(None);
+
+/// How API keys saved in storage are encrypted.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum KeyProtection {
+    /// With a random key kept in the key file, see [`key_file_path`].
+    KeyFile,
+
+    /// With a key derived from passphrase and the salt, base64. `check` is [`CHECK_TEXT`] encrypted with it.
+    Passphrase { salt: String, check: String },
+}
+
+impl KeyProtection {
+    /// Protection with the passphrase, a new salt is generated.
+    pub fn with_passphrase(passphrase: &str) -> Result<(Self, Cipher), WeatherError> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = Cipher::from_passphrase(passphrase, &salt)?;
+        let protection = KeyProtection::Passphrase {
+            salt: STANDARD.encode(salt),
+            check: cipher.encrypt(CHECK_TEXT),
+        };
+        Ok((protection, cipher))
+    }
+
+    /// Whether the passphrase is asked for to read API keys.
+    pub fn needs_passphrase(&self) -> bool {
+        matches!(self, KeyProtection::Passphrase { .. })
+    }
+
+    /// Cipher API keys are encrypted with. The key file is created if it is missing,
+    /// passphrase is taken from `passphrase` only if it is needed, e.g. [`read_passphrase`].
+    pub fn cipher(
+        &self,
+        passphrase: impl FnOnce() -> Result<String, WeatherError>,
+    ) -> Result<Cipher, WeatherError> {
+        match self {
+            KeyProtection::KeyFile => Cipher::from_key_file(&key_file_path()),
+            KeyProtection::Passphrase { salt, check } => {
+                let salt = STANDARD
+                    .decode(salt)
+                    .map_err(|_| WeatherError::ReadConfigFileError)?;
+                let cipher = Cipher::from_passphrase(&passphrase()?, &salt)?;
+                match cipher.decrypt(check) {
+                    Some(text) if text == CHECK_TEXT => Ok(cipher),
+                    _ => Err(WeatherError::WrongPassphraseError),
+                }
+            }
+        }
+    }
+}
+
+/// ChaCha20-Poly1305 cipher of API keys.
+#[derive(Clone)]
+pub struct Cipher(ChaCha20Poly1305);
+
+impl Cipher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        Cipher(ChaCha20Poly1305::new(Key::from_slice(key)))
+    }
+
+    /// Cipher with the key derived from passphrase by Argon2id.
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> Result<Self, WeatherError> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|error| WeatherError::KeyFileError(error.to_string()))?;
+        Ok(Cipher::new(&key))
+    }
+
+    /// Cipher with the key kept in the file, base64. A new random key is written
+    /// readable by the owner only if the file is missing.
+    pub fn from_key_file(path: &Path) -> Result<Self, WeatherError> {
+        let key_file_error = |error: std::io::Error| {
+            WeatherError::KeyFileError(format!("{}: {error}", path.display()))
+        };
+        if !path.exists() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(key_file_error)?;
+            }
+            let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            let mut file = options.open(path).map_err(key_file_error)?;
+            file.write_all(STANDARD.encode(key).as_bytes())
+                .map_err(key_file_error)?;
+        }
+        let key: [u8; 32] = STANDARD
+            .decode(fs::read_to_string(path).map_err(key_file_error)?.trim())
+            .ok()
+            .and_then(|key| key.try_into().ok())
+            .ok_or_else(|| {
+                WeatherError::KeyFileError(format!("{} holds no valid key", path.display()))
+            })?;
+        Ok(Cipher::new(&key))
+    }
+
+    /// Encrypts the text with a random nonce, see [`SEALED_PREFIX`].
+    pub fn encrypt(&self, text: &str) -> String {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut sealed = nonce.to_vec();
+        sealed.extend(
+            self.0
+                .encrypt(&nonce, text.as_bytes())
+                .expect("Encryption of a short text never fails"),
+        );
+        format!("{SEALED_PREFIX}{}", STANDARD.encode(sealed))
+    }
+
+    /// Decrypts text encrypted by [`Cipher::encrypt`]. `None` if it was encrypted with another key.
+    pub fn decrypt(&self, sealed: &str) -> Option<String> {
+        let sealed = STANDARD.decode(sealed.strip_prefix(SEALED_PREFIX)?).ok()?;
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let text = self.0.decrypt(Nonce::from_slice(nonce), ciphertext).ok()?;
+        String::from_utf8(text).ok()
+    }
+}
+
+/// Sets the cipher API keys are encrypted with, `None` locks them.
+pub fn unlock(cipher: Option<Cipher>) {
+    *CIPHER.write().expect("Cipher lock poisoned") = cipher;
+}
+
+/// API_KEY encrypted as it is saved. Fails while keys are locked rather than let it be saved in plain text.
+pub fn conceal(api_key: &str) -> Result<String, WeatherError> {
+    conceal_with(
+        CIPHER.read().expect("Cipher lock poisoned").as_ref(),
+        api_key,
+    )
+}
+
+fn conceal_with(cipher: Option<&Cipher>, api_key: &str) -> Result<String, WeatherError> {
+    cipher
+        .map(|cipher| cipher.encrypt(api_key))
+        .ok_or(WeatherError::ApiKeysLockedError)
+}
+
+/// API_KEY saved by [`conceal`]. `None` if it is encrypted and keys are locked or encrypted with another key.
+pub fn reveal(saved: &str) -> Option<String> {
+    if !is_sealed(saved) {
+        return Some(saved.to_owned());
+    }
+    CIPHER
+        .read()
+        .expect("Cipher lock poisoned")
+        .as_ref()?
+        .decrypt(saved)
+}
+
+/// Whether the saved API_KEY is encrypted.
+pub fn is_sealed(saved: &str) -> bool {
+    saved.starts_with(SEALED_PREFIX)
+}
+
+/// API_KEY with all but the last 4 characters hidden, fully hidden if it is short.
+pub fn mask(api_key: &str) -> String {
+    let chars: Vec<char> = api_key.chars().collect();
+    if chars.len() < 8 {
+        return "****".to_owned();
+    }
+    format!(
+        "****{}",
+        chars[chars.len() - 4..].iter().collect::<String>()
+    )
+}
+
+/// Path of the key file: [`KEY_FILE_VAR`] if set, `secret.key` in [`paths::config_dir`] otherwise.
+pub fn key_file_path() -> PathBuf {
+    match env::var_os(KEY_FILE_VAR) {
+        Some(path) => PathBuf::from(path),
+        None => paths::config_dir().join("secret.key"),
+    }
+}
+
+/// Passphrase of [`PASSPHRASE_VAR`] if set, prompted for without echo otherwise.
+pub fn read_passphrase(prompt: &str) -> Result<String, WeatherError> {
+    match env::var(PASSPHRASE_VAR) {
+        Ok(passphrase) => Ok(passphrase),
+        Err(_) => Ok(rpassword::prompt_password(prompt)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::{conceal_with, mask, Cipher, KeyProtection};
+    use crate::error::WeatherError;
+
+    #[test]
+    fn encrypted_with_random_nonce() {
+        let cipher = Cipher::new(&[7; 32]);
+        let sealed = cipher.encrypt("0123456789abcdef");
+        assert!(sealed.starts_with("enc:v1:"));
+        assert!(!sealed.contains("0123456789abcdef"));
+        assert_ne!(sealed, cipher.encrypt("0123456789abcdef"));
+        assert_eq!(Some("0123456789abcdef".to_owned()), cipher.decrypt(&sealed));
+        assert_eq!(None, Cipher::new(&[8; 32]).decrypt(&sealed));
+    }
+
+    #[test]
+    fn key_file_created_once() {
+        let path = env::temp_dir().join("weather_test_secret.key");
+        let _ = fs::remove_file(&path);
+        let sealed = Cipher::from_key_file(&path).unwrap().encrypt("key");
+        let cipher = Cipher::from_key_file(&path).unwrap();
+        assert_eq!(Some("key".to_owned()), cipher.decrypt(&sealed));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(0o600, mode & 0o777);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn wrong_passphrase_error_expected() {
+        let (protection, cipher) = KeyProtection::with_passphrase("correct horse").unwrap();
+        let sealed = cipher.encrypt("key");
+
+        assert!(matches!(
+            protection.cipher(|| Ok("battery staple".to_owned())),
+            Err(WeatherError::WrongPassphraseError)
+        ));
+        let cipher = protection
+            .cipher(|| Ok("correct horse".to_owned()))
+            .unwrap();
+        assert_eq!(Some("key".to_owned()), cipher.decrypt(&sealed));
+    }
+
+    #[test]
+    fn never_concealed_in_plain_text() {
+        assert_eq!(
+            Err(WeatherError::ApiKeysLockedError),
+            conceal_with(None, "key")
+        );
+        let cipher = Cipher::new(&[7; 32]);
+        let sealed = conceal_with(Some(&cipher), "key").unwrap();
+        assert_eq!(Some("key".to_owned()), cipher.decrypt(&sealed));
+    }
+
+    #[test]
+    fn masked_ok() {
+        assert_eq!("****cdef", mask("0123456789abcdef"));
+        assert_eq!("****", mask("short"));
+    }
+}
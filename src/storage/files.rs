@@ -0,0 +1,67 @@
+use std::{
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use fs2::FileExt;
+
+use crate::error::WeatherError;
+
+/// Writes a temporary file and renames it over the one at `path`, so the file is never left half written.
+/// The replaced one is kept as the backup.
+pub fn write_atomically(path: &Path, content: &[u8]) -> Result<(), WeatherError> {
+    let temp = sibling(path, "tmp");
+    let mut file = File::create(&temp)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    if path.exists() {
+        fs::copy(path, sibling(path, "bak"))?;
+    }
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Path next to the file with the suffix added to its name, e.g. `json_storage.json.bak`.
+pub fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Locks `.lock` file next to the storage one, the storage file itself is replaced on save.
+/// Waits for another process holding it.
+pub fn lock(path: &Path) -> Result<File, WeatherError> {
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(sibling(path, "lock"))?;
+    if let Err(error) = lock.try_lock_exclusive() {
+        if error.kind() != fs2::lock_contended_error().kind() {
+            return Err(error.into());
+        }
+        eprintln!(
+            "Waiting for another weather process to finish with {}",
+            path.display()
+        );
+        lock.lock_exclusive()?;
+    }
+    Ok(lock)
+}
+
+/// [`WeatherError::CorruptConfigFileError`] pointing at the backup if there is one.
+pub fn corrupt_file_error(path: &Path, error: &dyn Display) -> WeatherError {
+    let backup = sibling(path, "bak");
+    let advice = if backup.exists() {
+        format!(
+            "The previous version is kept in {}, restore it or move the file away to start over",
+            backup.display()
+        )
+    } else {
+        "There is no backup, fix the file or move it away to start over".to_owned()
+    };
+    WeatherError::CorruptConfigFileError(format!("{}: {error}. {advice}.", path.display()))
+}
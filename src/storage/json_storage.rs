@@ -1,59 +1,145 @@
-use std::{collections::HashMap, fs::OpenOptions, io::BufReader, path::Path};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    io::ErrorKind,
+    path::Path,
+};
 
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 
-use crate::{api::provider_api::WeatherProvider, error::WeatherError};
+use crate::{
+    api::{geocoder_api::Location, provider_api::WeatherProvider},
+    error::WeatherError,
+};
 
-use super::storage_api::Storage;
+use super::{
+    files::{corrupt_file_error, lock, sibling, write_atomically},
+    migrations,
+    storage_api::{Settings, Storage},
+};
 
 /// JSON storage implementation to hold provider entries in json file.
+/// The file is locked only while it is read or saved. Saving reads it again and keeps what other processes
+/// changed in between, along with the entries this one changed, see [`merge`].
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct JsonStorage {
+    /// Layout version the file is written in, see [`migrations`].
+    #[serde(default)]
+    version: u32,
+    #[serde(skip)]
     path: String,
+    /// Content as it was read or saved last, changes are told against it.
+    #[serde(skip)]
+    base: Value,
+    #[serde(default)]
     configs: HashMap<String, Box<dyn WeatherProvider>>,
     default: Option<String>,
+    #[serde(default)]
+    settings: Settings,
+    #[serde(default)]
+    locations: BTreeMap<String, Location>,
 }
 
 impl JsonStorage {
     pub fn new(path: &str) -> Result<Self, WeatherError> {
-        let file_exists = Path::new(path).exists();
-        let file = OpenOptions::new()
-            .write(true)
-            .read(true)
-            .append(true)
-            .create_new(!file_exists)
-            .open(path)?;
-
-        if file_exists && file.metadata()?.len() > 0 {
-            Ok(serde_json::from_reader(BufReader::new(&file))?)
-        } else {
-            Ok(JsonStorage {
-                path: path.to_owned(),
-                configs: HashMap::new(),
-                default: None,
-            })
+        let _lock = lock(Path::new(path))?;
+        let (content, original) = read(Path::new(path))?;
+        let storage = JsonStorage::from_content(path, content)?;
+        if let Some((version, original)) = original {
+            // The original is kept as it is in case the upgrade lost anything.
+            fs::write(
+                sibling(Path::new(path), &format!("v{version}.bak")),
+                original,
+            )?;
+            storage.write()?;
         }
+        Ok(storage)
     }
 
-    fn save(&self) -> Result<(), WeatherError> {
-        let file_exists = Path::new(&self.path).exists();
-        let file = OpenOptions::new()
-            .write(true)
-            .create_new(!file_exists)
-            .truncate(true)
-            .open(&self.path)?;
-        serde_json::to_writer(&file, &self)?;
-        Ok(())
+    fn from_content(path: &str, content: Value) -> Result<Self, WeatherError> {
+        let mut storage: JsonStorage = serde_json::from_value(content)
+            .map_err(|error| corrupt_file_error(Path::new(path), &error))?;
+        storage.path = path.to_owned();
+        storage.base = serde_json::to_value(&storage)?;
+        Ok(storage)
+    }
+
+    /// Saves the changes made since the file was read on top of its current content.
+    fn save(&mut self) -> Result<(), WeatherError> {
+        let path = Path::new(&self.path);
+        let _lock = lock(path)?;
+        let ours = serde_json::to_value(&*self)?;
+        let (mut content, _) = read(path)?;
+        if let (Some(base), Some(ours), Some(theirs)) = (
+            self.base.as_object(),
+            ours.as_object(),
+            content.as_object_mut(),
+        ) {
+            merge(base, ours, theirs, 2);
+        }
+        *self = JsonStorage::from_content(&self.path, content)?;
+        self.write()
+    }
+
+    fn write(&self) -> Result<(), WeatherError> {
+        write_atomically(Path::new(&self.path), &serde_json::to_vec(&self)?)
+    }
+}
+
+/// Version the content is upgraded from along with the original content.
+type Upgrade = (u32, Vec<u8>);
+
+/// Content of the file upgraded to the current version, along with the [`Upgrade`] if it is upgraded.
+/// Missing or empty file is an empty storage.
+fn read(path: &Path) -> Result<(Value, Option<Upgrade>), WeatherError> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == ErrorKind::NotFound => vec![],
+        Err(error) => return Err(error.into()),
+    };
+    if content.is_empty() {
+        return Ok((json!({ "version": migrations::SCHEMA_VERSION }), None));
+    }
+    let mut value: Value =
+        serde_json::from_slice(&content).map_err(|error| corrupt_file_error(path, &error))?;
+    let upgraded_from = migrations::migrate(&mut value)?;
+    Ok((value, upgraded_from.map(|version| (version, content))))
+}
+
+/// Puts the entries `ours` changed since `base` into `theirs`, the rest of `theirs` is kept. Objects are
+/// merged `depth` levels down, e.g. 2 for single providers, settings and locations of the file.
+fn merge(
+    base: &Map<String, Value>,
+    ours: &Map<String, Value>,
+    theirs: &mut Map<String, Value>,
+    depth: usize,
+) {
+    let keys: BTreeSet<&String> = base.keys().chain(ours.keys()).collect();
+    for key in keys {
+        let (old, new) = (base.get(key), ours.get(key));
+        if old == new {
+            continue;
+        }
+        match (old, new, theirs.get_mut(key)) {
+            (Some(Value::Object(old)), Some(Value::Object(new)), Some(Value::Object(current)))
+                if depth > 1 =>
+            {
+                merge(old, new, current, depth - 1)
+            }
+            (_, Some(new), _) => {
+                theirs.insert(key.clone(), new.clone());
+            }
+            (_, None, _) => {
+                theirs.remove(key);
+            }
+        }
     }
 }
 
 impl Storage for JsonStorage {
     fn get_all(&self) -> Vec<&dyn WeatherProvider> {
-        self.configs
-            .values()
-            .into_iter()
-            .map(|p| p.as_ref())
-            .collect()
+        self.configs.values().map(|p| p.as_ref()).collect()
     }
 
     fn add(
@@ -92,13 +178,52 @@ impl Storage for JsonStorage {
     fn get_default_entry(&mut self) -> Option<&mut Box<dyn WeatherProvider>> {
         self.default.clone().map_or_else(|| None, |f| self.get(&f))
     }
+
+    fn get_settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    fn set_settings(&mut self, settings: Settings) -> Result<(), WeatherError> {
+        self.settings = settings;
+        self.save()
+    }
+
+    fn get_locations(&self) -> Vec<(&str, &Location)> {
+        self.locations
+            .iter()
+            .map(|(name, location)| (name.as_str(), location))
+            .collect()
+    }
+
+    fn get_location(&self, name: &str) -> Option<&Location> {
+        self.locations.get(name)
+    }
+
+    fn add_location(&mut self, name: &str, location: Location) -> Result<(), WeatherError> {
+        self.locations.insert(name.to_owned(), location);
+        self.save()
+    }
+
+    fn delete_location(&mut self, name: &str) -> Result<(), WeatherError> {
+        self.locations
+            .remove(name)
+            .ok_or_else(|| WeatherError::NoSuchLocationError(name.to_owned()))?;
+        self.save()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Mutex;
+    use std::{fs, sync::Mutex};
 
-    use crate::{providers::openweather_api::OpenWeatherProvider, storage::storage_api::Storage};
+    use crate::{
+        api::{geocoder_api::Location, provider_api::WeatherProvider},
+        error::WeatherError,
+        providers::openweather_api::OpenWeatherProvider,
+        secrets::{self, Cipher, KeyProtection},
+        storage::storage_api::{Settings, Storage},
+        units::UnitSystem,
+    };
 
     use super::JsonStorage;
 
@@ -144,22 +269,176 @@ mod tests {
         let _lock = M.lock()?;
 
         let provider_name = "Weather Provider A for deletion";
-        let name_cloned = provider_name.clone();
+        let name_cloned = provider_name;
         let mut storage: Box<dyn Storage> = Box::new(JsonStorage::new(STORAGE_JSON_FILE).unwrap());
 
         let provider = OpenWeatherProvider::new(provider_name, None);
         storage.add(Box::new(provider)).unwrap();
 
-        let config = storage.get(&name_cloned).unwrap();
+        let config = storage.get(name_cloned).unwrap();
         assert_eq!(None, config.get_api_key());
 
-        storage.delete(&name_cloned).unwrap();
+        storage.delete(name_cloned).unwrap();
 
         let result = storage.get_default_entry();
         assert!(result.is_none());
 
-        let config = storage.get(&name_cloned);
+        let config = storage.get(name_cloned);
         assert!(config.is_none());
         Ok(())
     }
+
+    #[test]
+    fn settings_ok() -> TestResult {
+        let _lock = M.lock()?;
+
+        let mut storage: Box<dyn Storage> = Box::new(JsonStorage::new(STORAGE_JSON_FILE).unwrap());
+        let settings = Settings {
+            units: UnitSystem::Imperial,
+            geocoder: Some("Open-Meteo".to_owned()),
+            cities_file: Some("cities15000.txt".to_owned()),
+            fallback: vec!["OpenWeather".to_owned(), "Open-Meteo".to_owned()],
+            jobs: Some(8),
+            key_protection: Some(KeyProtection::KeyFile),
+        };
+        storage.set_settings(settings.clone()).unwrap();
+
+        let mut storage: Box<dyn Storage> = Box::new(JsonStorage::new(STORAGE_JSON_FILE).unwrap());
+        assert_eq!(&settings, storage.get_settings());
+
+        storage.set_settings(Settings::default()).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn locations_ok() -> TestResult {
+        let _lock = M.lock()?;
+
+        let mut storage: Box<dyn Storage> = Box::new(JsonStorage::new(STORAGE_JSON_FILE).unwrap());
+        let office = Location {
+            name: "Lviv".to_owned(),
+            lat: 49.8419,
+            lon: 24.0315,
+            provider_ids: [("accuweather".to_owned(), "324561".to_owned())].into(),
+            ..Default::default()
+        };
+        storage.add_location("office", office.clone()).unwrap();
+        storage.add_location("home", Location::default()).unwrap();
+        drop(storage);
+
+        let mut storage: Box<dyn Storage> = Box::new(JsonStorage::new(STORAGE_JSON_FILE).unwrap());
+        assert_eq!(Some(&office), storage.get_location("office"));
+        let names: Vec<&str> = storage
+            .get_locations()
+            .iter()
+            .map(|(name, _)| *name)
+            .collect();
+        assert_eq!(vec!["home", "office"], names);
+
+        storage.delete_location("home").unwrap();
+        storage.delete_location("office").unwrap();
+        assert!(storage.get_locations().is_empty());
+        assert_eq!(
+            Err(WeatherError::NoSuchLocationError("home".to_owned())),
+            storage.delete_location("home")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn api_key_saved_encrypted() -> TestResult {
+        let _lock = M.lock()?;
+
+        secrets::unlock(Some(Cipher::new(&[7; 32])));
+        let mut provider = OpenWeatherProvider::new("Encrypted OpenWeather", None);
+        provider.set_api_key("0123456789abcdef")?;
+        let mut storage = JsonStorage::new(STORAGE_JSON_FILE)?;
+        storage.add(Box::new(provider))?;
+        drop(storage);
+
+        assert!(!fs::read_to_string(STORAGE_JSON_FILE)?.contains("0123456789abcdef"));
+        let mut storage = JsonStorage::new(STORAGE_JSON_FILE)?;
+        let provider = storage.get("Encrypted OpenWeather").unwrap();
+        assert_eq!(Some("0123456789abcdef".to_owned()), provider.get_api_key());
+        storage.delete("Encrypted OpenWeather")?;
+        Ok(())
+    }
+
+    #[test]
+    fn changes_of_other_processes_kept() -> TestResult {
+        let _lock = M.lock()?;
+
+        let mut first = JsonStorage::new(STORAGE_JSON_FILE)?;
+        let mut second = JsonStorage::new(STORAGE_JSON_FILE)?;
+        first.add(Box::new(OpenWeatherProvider::new(
+            "First OpenWeather",
+            None,
+        )))?;
+        first.set_settings(Settings {
+            units: UnitSystem::Imperial,
+            ..Default::default()
+        })?;
+        // Settings read before the first one changed them.
+        second.set_settings(Settings {
+            jobs: Some(2),
+            ..second.get_settings().clone()
+        })?;
+        second.add_location("home", Location::default())?;
+        assert!(second.get("First OpenWeather").is_some());
+
+        let mut storage = JsonStorage::new(STORAGE_JSON_FILE)?;
+        assert_eq!(UnitSystem::Imperial, storage.get_settings().units);
+        assert_eq!(Some(2), storage.get_settings().jobs);
+        assert!(storage.get_location("home").is_some());
+
+        storage.delete("First OpenWeather")?;
+        storage.delete_location("home")?;
+        storage.set_settings(Settings::default())?;
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_file_points_at_backup() -> TestResult {
+        let _lock = M.lock()?;
+
+        let mut storage = JsonStorage::new(STORAGE_JSON_FILE).unwrap();
+        storage.set_settings(Settings::default()).unwrap();
+        storage.set_settings(Settings::default()).unwrap();
+        drop(storage);
+        assert!(!std::path::Path::new("test_json_storage.json.tmp").exists());
+        let saved = fs::read_to_string(STORAGE_JSON_FILE)?;
+        assert_eq!(saved, fs::read_to_string("test_json_storage.json.bak")?);
+
+        fs::write(STORAGE_JSON_FILE, &saved[..saved.len() / 2])?;
+        match JsonStorage::new(STORAGE_JSON_FILE) {
+            Err(WeatherError::CorruptConfigFileError(message)) => {
+                assert!(message.contains("test_json_storage.json.bak, restore it"))
+            }
+            other => panic!("Unexpected result {other:?}"),
+        }
+        fs::write(STORAGE_JSON_FILE, saved)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unversioned_file_upgraded_with_backup() -> TestResult {
+        let _lock = M.lock()?;
+
+        let legacy = r#"{"path":"json_storage.json","configs":{},"default":null,"settings":{"units":"imperial"}}"#;
+        fs::write(STORAGE_JSON_FILE, legacy)?;
+        let storage = JsonStorage::new(STORAGE_JSON_FILE).unwrap();
+        assert_eq!(UnitSystem::Imperial, storage.get_settings().units);
+        drop(storage);
+
+        assert_eq!(legacy, fs::read_to_string("test_json_storage.json.v0.bak")?);
+        let saved: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(STORAGE_JSON_FILE)?)?;
+        assert_eq!(1, saved["version"]);
+        assert!(saved.get("path").is_none());
+
+        let mut storage = JsonStorage::new(STORAGE_JSON_FILE).unwrap();
+        storage.set_settings(Settings::default()).unwrap();
+        fs::remove_file("test_json_storage.json.v0.bak")?;
+        Ok(())
+    }
 }
@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    api::{geocoder_api::Location, provider_api::WeatherProvider},
+    error::WeatherError,
+};
+
+use super::storage_api::Settings;
+
+/// Version of the configuration layout this build writes. Files without version are of version 0.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Configuration laid out the way [`super::json_storage::JsonStorage`] writes it. Other storages
+/// turn their content into this layout to [`migrate`] it and deserialize.
+#[derive(Deserialize)]
+pub struct Content {
+    #[serde(default)]
+    pub configs: BTreeMap<String, Box<dyn WeatherProvider>>,
+    pub default: Option<String>,
+    #[serde(default)]
+    pub settings: Settings,
+    #[serde(default)]
+    pub locations: BTreeMap<String, Location>,
+}
+
+/// Upgrades the content of the configuration file by one version.
+type Migration = fn(&mut Map<String, Value>);
+
+/// Migration from version `i` to `i + 1` is at index `i`. When a provider struct gains a field
+/// with no sensible default or renames one, bump [`SCHEMA_VERSION`] and add the step here.
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [drop_path];
+
+/// Upgrades the content step by step up to [`SCHEMA_VERSION`]. The version it had if it is upgraded,
+/// `None` if it is up to date or is not a configuration at all, which is left to deserialization to tell.
+pub fn migrate(content: &mut Value) -> Result<Option<u32>, WeatherError> {
+    let Some(object) = content.as_object_mut() else {
+        return Ok(None);
+    };
+    let version = match object.get("version") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| WeatherError::ConfigVersionError(version.to_string()))?,
+    };
+    if version > SCHEMA_VERSION {
+        return Err(WeatherError::ConfigVersionError(version.to_string()));
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(object);
+    }
+    object.insert("version".to_owned(), SCHEMA_VERSION.into());
+    Ok((version < SCHEMA_VERSION).then_some(version))
+}
+
+/// 0 to 1: the file no longer keeps its own path, it went stale once the file was moved.
+fn drop_path(object: &mut Map<String, Value>) {
+    object.remove("path");
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{migrate, SCHEMA_VERSION};
+    use crate::error::WeatherError;
+
+    #[test]
+    fn unversioned_file_upgraded() {
+        let mut content = json!({
+            "path": "json_storage.json",
+            "configs": {},
+            "default": null
+        });
+        assert_eq!(Some(0), migrate(&mut content).unwrap());
+        assert_eq!(
+            json!({"configs": {}, "default": null, "version": SCHEMA_VERSION}),
+            content
+        );
+        assert_eq!(None, migrate(&mut content).unwrap());
+    }
+
+    #[test]
+    fn newer_version_error_expected() {
+        let mut content = json!({ "version": SCHEMA_VERSION + 1 });
+        assert_eq!(
+            Err(WeatherError::ConfigVersionError(
+                (SCHEMA_VERSION + 1).to_string()
+            )),
+            migrate(&mut content)
+        );
+        assert!(migrate(&mut json!({ "version": "one" })).is_err());
+    }
+}
@@ -1,2 +1,7 @@
 pub mod storage_api;
-pub mod json_storage;
\ No newline at end of file
+pub mod json_storage;
+pub mod files;
+pub mod paths;
+pub mod migrations;
+pub mod toml_storage;
+pub mod sqlite_storage;
\ No newline at end of file
@@ -0,0 +1,131 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::error::WeatherError;
+
+/// Environment variable overriding the path of the configuration file.
+pub const CONFIG_VAR: &str = "WEATHER_CONFIG";
+
+/// Name of the configuration file, also the one older versions kept in the working directory.
+pub const STORAGE_FILE_NAME: &str = "json_storage.json";
+
+/// Directory the app keeps its files in: `$XDG_CONFIG_HOME/weather`, `~/.config/weather` if it is not set.
+/// Relative `$XDG_CONFIG_HOME` is ignored as the spec requires.
+pub fn config_dir() -> PathBuf {
+    config_dir_from(env::var_os("XDG_CONFIG_HOME"), dirs::home_dir())
+}
+
+/// [`config_dir`] given the values of `$XDG_CONFIG_HOME` and the home directory.
+fn config_dir_from(xdg_config_home: Option<OsString>, home: Option<PathBuf>) -> PathBuf {
+    let base = xdg_config_home
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .unwrap_or_else(|| home.unwrap_or_default().join(".config"));
+    base.join("weather")
+}
+
+/// Path of the configuration file: the one given by `--config` if any, [`CONFIG_VAR`] if set,
+/// [`STORAGE_FILE_NAME`] in [`config_dir`] otherwise.
+pub fn storage_path(config: Option<&Path>) -> PathBuf {
+    storage_path_from(config, env::var_os(CONFIG_VAR), config_dir())
+}
+
+/// [`storage_path`] given the value of [`CONFIG_VAR`] and the config directory.
+fn storage_path_from(
+    config: Option<&Path>,
+    env_config: Option<OsString>,
+    config_dir: PathBuf,
+) -> PathBuf {
+    config
+        .map(Path::to_path_buf)
+        .or_else(|| env_config.map(PathBuf::from))
+        .unwrap_or_else(|| config_dir.join(STORAGE_FILE_NAME))
+}
+
+/// Moves the configuration file older versions left in the working directory to `path`
+/// unless there is one already. Whether it is moved.
+pub fn migrate(legacy: &Path, path: &Path) -> Result<bool, WeatherError> {
+    if path.exists() || !legacy.is_file() {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Rename fails across file systems, e.g. when the home directory is mounted separately.
+    if fs::rename(legacy, path).is_err() {
+        fs::copy(legacy, path)?;
+        fs::remove_file(legacy)?;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        path::{Path, PathBuf},
+    };
+
+    use super::{config_dir_from, migrate, storage_path_from};
+
+    #[test]
+    fn config_dir_of_xdg_or_home() {
+        let home = Some(PathBuf::from("/home/user"));
+        assert_eq!(
+            PathBuf::from("/tmp/xdg/weather"),
+            config_dir_from(Some("/tmp/xdg".into()), home.clone())
+        );
+        assert_eq!(
+            PathBuf::from("/home/user/.config/weather"),
+            config_dir_from(Some("relative".into()), home.clone())
+        );
+        assert_eq!(
+            PathBuf::from("/home/user/.config/weather"),
+            config_dir_from(None, home)
+        );
+    }
+
+    #[test]
+    fn storage_path_precedence() {
+        let dir = PathBuf::from("/tmp/xdg/weather");
+        assert_eq!(
+            PathBuf::from("/tmp/xdg/weather/json_storage.json"),
+            storage_path_from(None, None, dir.clone())
+        );
+        assert_eq!(
+            PathBuf::from("/etc/weather.json"),
+            storage_path_from(None, Some("/etc/weather.json".into()), dir.clone())
+        );
+        assert_eq!(
+            PathBuf::from("mine.json"),
+            storage_path_from(
+                Some(Path::new("mine.json")),
+                Some("/etc/weather.json".into()),
+                dir
+            )
+        );
+    }
+
+    #[test]
+    fn working_dir_file_migrated_once() {
+        let dir = env::temp_dir().join("weather_test_migrate");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let legacy = dir.join("json_storage.json");
+        let path = dir.join("config").join("weather").join("json_storage.json");
+        fs::write(&legacy, "{}").unwrap();
+
+        assert!(migrate(&legacy, &path).unwrap());
+        assert!(!legacy.exists());
+        assert_eq!("{}", fs::read_to_string(&path).unwrap());
+
+        fs::write(&legacy, "{\"default\":null}").unwrap();
+        assert!(!migrate(&legacy, &path).unwrap());
+        assert_eq!("{}", fs::read_to_string(&path).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
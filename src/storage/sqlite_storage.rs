@@ -0,0 +1,504 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    time::Duration,
+};
+
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde_json::{Map, Value};
+
+use crate::{
+    api::{geocoder_api::Location, provider_api::WeatherProvider},
+    error::WeatherError,
+};
+
+use super::{
+    files::{corrupt_file_error, sibling},
+    migrations::{self, Content},
+    storage_api::{Settings, Storage},
+};
+
+/// Tables of the database. Provider entries and locations are kept as JSON, the way other storages
+/// write them, so [`migrations`] upgrade them alike.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS providers (
+        name TEXT PRIMARY KEY,
+        config TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS locations (
+        name TEXT PRIMARY KEY,
+        location TEXT NOT NULL
+    );
+";
+
+/// How long a write waits for another process writing the database.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Row level change of the database.
+enum Change {
+    PutMeta(&'static str, String),
+    DeleteMeta(&'static str),
+    /// Settings fields changed, `None` for those left unset. Other fields are kept as the database has them.
+    MergeSettings(Vec<(String, Option<Value>)>),
+    PutProvider(String, String),
+    DeleteProvider(String),
+    PutLocation(String, String),
+    DeleteLocation(String),
+}
+
+/// SQLite storage implementation. Every change is a transaction touching the rows it changes only,
+/// so processes sharing the database, e.g. a daemon and the CLI, don't overwrite each other's changes
+/// the way whole file rewrites do.
+#[derive(Debug)]
+pub(crate) struct SqliteStorage {
+    path: String,
+    connection: Connection,
+    configs: BTreeMap<String, Box<dyn WeatherProvider>>,
+    default: Option<String>,
+    settings: Settings,
+    locations: BTreeMap<String, Location>,
+    /// JSON of every provider as the database has it. Providers may be changed in place through
+    /// [`Storage::get`], those differing are written along with the next change.
+    saved_configs: BTreeMap<String, String>,
+}
+
+impl SqliteStorage {
+    pub fn new(path: &str) -> Result<Self, WeatherError> {
+        let connection = Connection::open(path)?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        // Readers don't wait for the writer.
+        connection.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        connection.execute_batch(SCHEMA)?;
+        let mut storage = SqliteStorage {
+            path: path.to_owned(),
+            connection,
+            configs: BTreeMap::new(),
+            default: None,
+            settings: Settings::default(),
+            locations: BTreeMap::new(),
+            saved_configs: BTreeMap::new(),
+        };
+        storage.load()?;
+        Ok(storage)
+    }
+
+    /// Reads all the rows. A new database is stamped with the current version, an older one is upgraded
+    /// by [`migrations`] and rewritten, the original is kept as `<path>.v<version>.bak`.
+    fn load(&mut self) -> Result<(), WeatherError> {
+        let version: Option<String> = self
+            .connection
+            .query_row("SELECT value FROM meta WHERE key = 'version'", [], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        let mut content = Map::new();
+        if let Some(version) = version {
+            content.insert("version".to_owned(), self.parse(&version)?);
+        } else if self.rows("providers")?.is_empty() && self.rows("locations")?.is_empty() {
+            return self.apply(vec![Change::PutMeta(
+                "version",
+                migrations::SCHEMA_VERSION.to_string(),
+            )]);
+        }
+        for key in ["default", "settings"] {
+            if let Some(value) = self.meta(key)? {
+                content.insert(key.to_owned(), self.parse(&value)?);
+            }
+        }
+        for (key, table) in [("configs", "providers"), ("locations", "locations")] {
+            let mut rows = Map::new();
+            for (name, value) in self.rows(table)? {
+                rows.insert(name.clone(), self.parse(&value)?);
+                if table == "providers" {
+                    self.saved_configs.insert(name, value);
+                }
+            }
+            content.insert(key.to_owned(), Value::Object(rows));
+        }
+
+        let mut content = Value::Object(content);
+        let upgraded_from = migrations::migrate(&mut content)?;
+        let content: Content = serde_json::from_value(content)
+            .map_err(|error| corrupt_file_error(Path::new(&self.path), &error))?;
+        self.configs = content.configs;
+        self.default = content.default;
+        self.settings = content.settings;
+        self.locations = content.locations;
+
+        if let Some(version) = upgraded_from {
+            let backup = sibling(Path::new(&self.path), &format!("v{version}.bak"));
+            self.connection
+                .execute("VACUUM INTO ?1", params![backup.to_string_lossy()])?;
+            let mut changes = vec![
+                Change::PutMeta("version", migrations::SCHEMA_VERSION.to_string()),
+                Change::PutMeta("settings", serde_json::to_string(&self.settings)?),
+            ];
+            for (name, location) in &self.locations {
+                changes.push(Change::PutLocation(
+                    name.clone(),
+                    serde_json::to_string(location)?,
+                ));
+            }
+            self.apply(changes)?;
+        }
+        Ok(())
+    }
+
+    fn meta(&self, key: &str) -> Result<Option<String>, WeatherError> {
+        Ok(self
+            .connection
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    /// Name and JSON of every row of `providers` or `locations` table.
+    fn rows(&self, table: &str) -> Result<Vec<(String, String)>, WeatherError> {
+        let column = if table == "providers" {
+            "config"
+        } else {
+            "location"
+        };
+        let mut statement = self
+            .connection
+            .prepare(&format!("SELECT name, {column} FROM {table} ORDER BY name"))?;
+        let rows = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        Ok(rows)
+    }
+
+    fn parse(&self, json: &str) -> Result<Value, WeatherError> {
+        serde_json::from_str(json)
+            .map_err(|error| corrupt_file_error(Path::new(&self.path), &error))
+    }
+
+    /// Applies the changes along with changed providers in a single transaction.
+    fn apply(&mut self, mut changes: Vec<Change>) -> Result<(), WeatherError> {
+        let mut changed_configs = vec![];
+        for (name, provider) in &self.configs {
+            let config = serde_json::to_string(provider)?;
+            if self.saved_configs.get(name) != Some(&config) {
+                changes.push(Change::PutProvider(name.clone(), config.clone()));
+                changed_configs.push((name.clone(), config));
+            }
+        }
+        let mut merged_settings = None;
+        let corrupt = |error: serde_json::Error| corrupt_file_error(Path::new(&self.path), &error);
+        // Takes the write lock at once, what is read below stays current until the commit.
+        let transaction = self
+            .connection
+            .transaction_with_behavior(TransactionBehavior::Immediate)?;
+        for change in changes {
+            match change {
+                Change::PutMeta(key, value) => transaction.execute(
+                    "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+                    params![key, value],
+                )?,
+                Change::DeleteMeta(key) => {
+                    transaction.execute("DELETE FROM meta WHERE key = ?1", [key])?
+                }
+                Change::MergeSettings(fields) => {
+                    let saved: Option<String> = transaction
+                        .query_row("SELECT value FROM meta WHERE key = 'settings'", [], |row| {
+                            row.get(0)
+                        })
+                        .optional()?;
+                    let mut settings = match saved
+                        .map(|saved| serde_json::from_str(&saved))
+                        .transpose()
+                        .map_err(corrupt)?
+                    {
+                        Some(Value::Object(settings)) => settings,
+                        _ => Map::new(),
+                    };
+                    for (field, value) in fields {
+                        match value {
+                            Some(value) => settings.insert(field, value),
+                            None => settings.remove(&field),
+                        };
+                    }
+                    let settings = Value::Object(settings);
+                    merged_settings = Some(
+                        serde_json::from_value::<Settings>(settings.clone()).map_err(corrupt)?,
+                    );
+                    transaction.execute(
+                        "INSERT OR REPLACE INTO meta (key, value) VALUES ('settings', ?1)",
+                        [settings.to_string()],
+                    )?
+                }
+                Change::PutProvider(name, config) => transaction.execute(
+                    "INSERT OR REPLACE INTO providers (name, config) VALUES (?1, ?2)",
+                    params![name, config],
+                )?,
+                Change::DeleteProvider(name) => {
+                    transaction.execute("DELETE FROM providers WHERE name = ?1", [name])?
+                }
+                Change::PutLocation(name, location) => transaction.execute(
+                    "INSERT OR REPLACE INTO locations (name, location) VALUES (?1, ?2)",
+                    params![name, location],
+                )?,
+                Change::DeleteLocation(name) => {
+                    transaction.execute("DELETE FROM locations WHERE name = ?1", [name])?
+                }
+            };
+        }
+        transaction.commit()?;
+        self.saved_configs.extend(changed_configs);
+        if let Some(settings) = merged_settings {
+            self.settings = settings;
+        }
+        Ok(())
+    }
+
+    fn default_change(&self) -> Result<Change, WeatherError> {
+        Ok(match &self.default {
+            Some(default) => Change::PutMeta("default", serde_json::to_string(default)?),
+            None => Change::DeleteMeta("default"),
+        })
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn get_all(&self) -> Vec<&dyn WeatherProvider> {
+        self.configs.values().map(|p| p.as_ref()).collect()
+    }
+
+    fn add(&mut self, provider: Box<dyn WeatherProvider>) -> Result<(), WeatherError> {
+        self.configs.insert(provider.get_name(), provider);
+        self.apply(vec![])
+    }
+
+    fn get(&mut self, key: &str) -> Option<&mut Box<dyn WeatherProvider>> {
+        self.configs.get_mut(key)
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), WeatherError> {
+        self.configs.remove(key);
+        self.saved_configs.remove(key);
+        if self.default.as_deref() == Some(key) {
+            self.default = None
+        }
+        let changes = vec![
+            Change::DeleteProvider(key.to_owned()),
+            self.default_change()?,
+        ];
+        self.apply(changes)
+    }
+
+    fn set_default_entry(&mut self, key: &str) -> Result<(), WeatherError> {
+        self.default = Some(
+            self.configs
+                .get(key)
+                .map(|provider| provider.get_name())
+                .ok_or(WeatherError::NoSuchProviderError)?,
+        );
+        let change = self.default_change()?;
+        self.apply(vec![change])
+    }
+
+    fn get_default_entry(&mut self) -> Option<&mut Box<dyn WeatherProvider>> {
+        self.default.clone().and_then(|name| self.get(&name))
+    }
+
+    fn get_settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Writes only the fields differing from the settings loaded, so fields another process changed
+    /// in the meantime are kept.
+    fn set_settings(&mut self, settings: Settings) -> Result<(), WeatherError> {
+        let (old, new) = (
+            serde_json::to_value(&self.settings)?,
+            serde_json::to_value(&settings)?,
+        );
+        let (old, new) = (old.as_object(), new.as_object());
+        let fields: BTreeSet<&String> = old.into_iter().chain(new).flat_map(Map::keys).collect();
+        let changed = fields
+            .into_iter()
+            .map(|field| {
+                (
+                    field,
+                    old.and_then(|old| old.get(field)),
+                    new.and_then(|new| new.get(field)),
+                )
+            })
+            .filter(|(_, old, new)| old != new)
+            .map(|(field, _, new)| (field.clone(), new.cloned()))
+            .collect();
+        self.settings = settings;
+        self.apply(vec![Change::MergeSettings(changed)])
+    }
+
+    fn get_locations(&self) -> Vec<(&str, &Location)> {
+        self.locations
+            .iter()
+            .map(|(name, location)| (name.as_str(), location))
+            .collect()
+    }
+
+    fn get_location(&self, name: &str) -> Option<&Location> {
+        self.locations.get(name)
+    }
+
+    fn add_location(&mut self, name: &str, location: Location) -> Result<(), WeatherError> {
+        let change = Change::PutLocation(name.to_owned(), serde_json::to_string(&location)?);
+        self.locations.insert(name.to_owned(), location);
+        self.apply(vec![change])
+    }
+
+    fn delete_location(&mut self, name: &str) -> Result<(), WeatherError> {
+        self.locations
+            .remove(name)
+            .ok_or_else(|| WeatherError::NoSuchLocationError(name.to_owned()))?;
+        self.apply(vec![Change::DeleteLocation(name.to_owned())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::Mutex};
+
+    use crate::{
+        api::geocoder_api::Location,
+        error::WeatherError,
+        providers::openweather_api::OpenWeatherProvider,
+        storage::storage_api::{Settings, Storage},
+        units::UnitSystem,
+    };
+
+    use super::SqliteStorage;
+
+    type TestResult<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+    // Need to sync test threads sharing the database
+    static M: Mutex<()> = Mutex::new(());
+
+    static STORAGE_DB_FILE: &str = "test_sqlite_storage.db";
+
+    fn remove_database() {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{STORAGE_DB_FILE}{suffix}"));
+        }
+    }
+
+    #[test]
+    fn providers_settings_and_locations_ok() -> TestResult {
+        let _lock = M.lock()?;
+        remove_database();
+
+        let mut storage = SqliteStorage::new(STORAGE_DB_FILE).unwrap();
+        storage
+            .add(Box::new(OpenWeatherProvider::new("OpenWeather", None)))
+            .unwrap();
+        storage.set_default_entry("OpenWeather").unwrap();
+        let settings = Settings {
+            units: UnitSystem::Imperial,
+            jobs: Some(8),
+            ..Default::default()
+        };
+        storage.set_settings(settings.clone()).unwrap();
+        let office = Location {
+            name: "Lviv".to_owned(),
+            lat: 49.8419,
+            lon: 24.0315,
+            ..Default::default()
+        };
+        storage.add_location("office", office.clone()).unwrap();
+        // Changed in place, written with the next change.
+        storage
+            .get("OpenWeather")
+            .unwrap()
+            .set_base_url(Some("http://localhost:8080"));
+        storage.add_location("home", Location::default()).unwrap();
+
+        let mut storage = SqliteStorage::new(STORAGE_DB_FILE).unwrap();
+        let provider = storage.get_default_entry().unwrap();
+        assert_eq!("http://localhost:8080", provider.get_base_url());
+        assert_eq!(&settings, storage.get_settings());
+        assert_eq!(Some(&office), storage.get_location("office"));
+
+        storage.delete("OpenWeather").unwrap();
+        storage.delete_location("home").unwrap();
+        let mut storage = SqliteStorage::new(STORAGE_DB_FILE).unwrap();
+        assert!(storage.get_all().is_empty());
+        assert!(storage.get_default_entry().is_none());
+        assert_eq!(
+            Err(WeatherError::NoSuchLocationError("home".to_owned())),
+            storage.delete_location("home")
+        );
+        remove_database();
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_changes_kept() -> TestResult {
+        let _lock = M.lock()?;
+        remove_database();
+
+        SqliteStorage::new(STORAGE_DB_FILE)
+            .unwrap()
+            .add(Box::new(OpenWeatherProvider::new("OpenWeather", None)))
+            .unwrap();
+        let mut cli = SqliteStorage::new(STORAGE_DB_FILE).unwrap();
+        let mut daemon = SqliteStorage::new(STORAGE_DB_FILE).unwrap();
+        cli.get("OpenWeather")
+            .unwrap()
+            .set_base_url(Some("http://localhost:8080"));
+        cli.add_location("office", Location::default()).unwrap();
+        // Looked at only, the stale copy is not written back.
+        assert!(daemon.get("OpenWeather").is_some());
+        daemon
+            .set_settings(Settings {
+                units: UnitSystem::Imperial,
+                ..Default::default()
+            })
+            .unwrap();
+
+        let mut storage = SqliteStorage::new(STORAGE_DB_FILE).unwrap();
+        assert!(storage.get_location("office").is_some());
+        assert_eq!(UnitSystem::Imperial, storage.get_settings().units);
+        assert_eq!(
+            "http://localhost:8080",
+            storage.get("OpenWeather").unwrap().get_base_url()
+        );
+        remove_database();
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_settings_merged() -> TestResult {
+        let _lock = M.lock()?;
+        remove_database();
+
+        let mut cli = SqliteStorage::new(STORAGE_DB_FILE).unwrap();
+        let mut daemon = SqliteStorage::new(STORAGE_DB_FILE).unwrap();
+        cli.set_settings(Settings {
+            units: UnitSystem::Imperial,
+            ..cli.get_settings().clone()
+        })
+        .unwrap();
+        // Settings read before the CLI changed them.
+        daemon
+            .set_settings(Settings {
+                fallback: vec!["Open-Meteo".to_owned()],
+                ..daemon.get_settings().clone()
+            })
+            .unwrap();
+        assert_eq!(UnitSystem::Imperial, daemon.get_settings().units);
+
+        let storage = SqliteStorage::new(STORAGE_DB_FILE).unwrap();
+        assert_eq!(UnitSystem::Imperial, storage.get_settings().units);
+        assert_eq!(
+            vec!["Open-Meteo".to_owned()],
+            storage.get_settings().fallback
+        );
+        remove_database();
+        Ok(())
+    }
+}
@@ -1,7 +1,43 @@
-use crate::{api::provider_api::WeatherProvider, error::WeatherError};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{geocoder_api::Location, provider_api::WeatherProvider},
+    error::WeatherError,
+    secrets::KeyProtection,
+    units::UnitSystem,
+};
+
+/// Application wide settings kept by storage along with providers.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Settings {
+    /// Unit system reports are shown in unless overridden per call.
+    #[serde(default)]
+    pub units: UnitSystem,
+
+    /// Provider whose geocoder resolves addresses for every provider. Each provider uses its own if not set.
+    #[serde(default)]
+    pub geocoder: Option<String>,
+
+    /// GeoNames cities file addresses are resolved with offline. Takes precedence over `geocoder`.
+    #[serde(default)]
+    pub cities_file: Option<String>,
+
+    /// Providers tried in turn when the default one fails to answer.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback: Vec<String>,
+
+    /// Most requests sent at once when several providers or addresses are asked.
+    #[serde(default)]
+    pub jobs: Option<usize>,
+
+    /// How API keys are encrypted. Not set by files saved before keys were encrypted.
+    #[serde(default)]
+    pub key_protection: Option<KeyProtection>,
+}
 
 /// Contains an API every kind of storage should implement.
-/// The entity the storage is currently implemented to store is one which implements [`WeatherProvider`].
+/// The entity the storage is currently implemented to store is one which implements [`WeatherProvider`],
+/// along with named [`Location`]s of the location book.
 pub trait Storage {
     /// Get all providers.
     fn get_all(&self) -> Vec<&dyn WeatherProvider>;
@@ -13,6 +49,7 @@ pub trait Storage {
     fn get(&mut self, key: &str) -> Option<&mut Box<dyn WeatherProvider>>;
 
     /// Delete provider by its name.
+    #[allow(dead_code)]
     fn delete(&mut self, key: &str) -> Result<(), WeatherError>;
 
     /// Set default entry.
@@ -20,4 +57,22 @@ pub trait Storage {
 
     /// Get default entry.
     fn get_default_entry(&mut self) -> Option<&mut Box<dyn WeatherProvider>>;
+
+    /// Get application settings.
+    fn get_settings(&self) -> &Settings;
+
+    /// Replace application settings.
+    fn set_settings(&mut self, settings: Settings) -> Result<(), WeatherError>;
+
+    /// Get all saved locations ordered by their names.
+    fn get_locations(&self) -> Vec<(&str, &Location)>;
+
+    /// Get a saved location by its name.
+    fn get_location(&self, name: &str) -> Option<&Location>;
+
+    /// Save a location under the name replacing the one saved before.
+    fn add_location(&mut self, name: &str, location: Location) -> Result<(), WeatherError>;
+
+    /// Delete saved location by its name.
+    fn delete_location(&mut self, name: &str) -> Result<(), WeatherError>;
 }
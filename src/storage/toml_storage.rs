@@ -0,0 +1,563 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::ErrorKind,
+    path::Path,
+};
+
+use serde::Serialize;
+use serde_json::{Map, Number, Value as JsonValue};
+use toml_edit::{DocumentMut, InlineTable, Item, Table, Value};
+
+use crate::{
+    api::{geocoder_api::Location, provider_api::WeatherProvider},
+    error::WeatherError,
+};
+
+use super::{
+    files::{corrupt_file_error, lock, sibling, write_atomically},
+    migrations::{self, Content},
+    storage_api::{Settings, Storage},
+};
+
+/// Field telling provider's type, e.g. `type = "OpenWeatherProvider"`.
+const TYPE_KEY: &str = "type";
+
+/// TOML storage implementation meant to be edited by hand and kept in git:
+///
+/// ```toml
+/// version = 1
+/// default = "OpenWeather"
+///
+/// [settings]
+/// units = "metric"
+///
+/// [providers.OpenWeather]
+/// type = "OpenWeatherProvider"
+/// api_key = "enc:v1:..."
+///
+/// [locations.office]
+/// name = "Lviv"
+/// ```
+///
+/// Saving rewrites only the values that changed, comments and formatting of the rest are kept.
+/// The file is locked only while it is read or saved, as [`super::json_storage::JsonStorage`] one is,
+/// and saving keeps the changes other processes made in the meantime.
+#[derive(Debug)]
+pub(crate) struct TomlStorage {
+    path: String,
+    /// Layout of the state as it was last read or saved, only what differs from it is saved.
+    base: Table,
+    configs: BTreeMap<String, Box<dyn WeatherProvider>>,
+    default: Option<String>,
+    settings: Settings,
+    locations: BTreeMap<String, Location>,
+}
+
+impl TomlStorage {
+    pub fn new(path: &str) -> Result<Self, WeatherError> {
+        let _lock = lock(Path::new(path))?;
+        let (text, mut document) = read(Path::new(path))?;
+        let (storage, upgraded_from) = Self::from_document(path, &document)?;
+        if let Some(version) = upgraded_from {
+            fs::write(sibling(Path::new(path), &format!("v{version}.bak")), &text)?;
+            merge_table(document.as_table_mut(), storage.layout()?);
+            write_atomically(Path::new(path), document.to_string().as_bytes())?;
+        }
+        Ok(storage)
+    }
+
+    /// Storage of the document's state, along with the version it was upgraded from if it was.
+    fn from_document(
+        path: &str,
+        document: &DocumentMut,
+    ) -> Result<(Self, Option<u32>), WeatherError> {
+        let mut storage = TomlStorage {
+            path: path.to_owned(),
+            base: Table::new(),
+            configs: BTreeMap::new(),
+            default: None,
+            settings: Settings::default(),
+            locations: BTreeMap::new(),
+        };
+        let mut upgraded_from = None;
+        if !document.is_empty() {
+            let mut value = from_layout(item_to_json(document.as_item()));
+            upgraded_from = migrations::migrate(&mut value)?;
+            let content: Content = serde_json::from_value(value)
+                .map_err(|error| corrupt_file_error(Path::new(path), &error))?;
+            storage.configs = content.configs;
+            storage.default = content.default;
+            storage.settings = content.settings;
+            storage.locations = content.locations;
+        }
+        storage.base = storage.layout()?;
+        Ok((storage, upgraded_from))
+    }
+
+    /// Reads the file again under the lock, applies what this storage changed to it and writes it
+    /// the way JSON storage does. The storage then holds the merged state.
+    fn save(&mut self) -> Result<(), WeatherError> {
+        let path = self.path.clone();
+        let _lock = lock(Path::new(&path))?;
+        let (_, mut document) = read(Path::new(&path))?;
+        let layout = self.layout()?;
+        if document.is_empty() {
+            merge_table(document.as_table_mut(), layout);
+        } else {
+            merge_changes(&self.base, layout, document.as_table_mut(), 2);
+        }
+        let (storage, _) = Self::from_document(&path, &document)?;
+        *self = storage;
+        write_atomically(Path::new(&path), document.to_string().as_bytes())
+    }
+
+    /// The whole state laid out as it is written, see [`TomlStorage`].
+    fn layout(&self) -> Result<Table, WeatherError> {
+        let mut root = Table::new();
+        root.insert(
+            "version",
+            Item::Value(i64::from(migrations::SCHEMA_VERSION).into()),
+        );
+        if let Some(default) = &self.default {
+            root.insert("default", Item::Value(default.into()));
+        }
+        root.insert("settings", Item::Table(table(to_json(&self.settings)?)));
+
+        let mut providers = Table::new();
+        providers.set_implicit(true);
+        for (name, provider) in &self.configs {
+            // Tagged by typetag as `{"OpenWeatherProvider": {...}}`.
+            if let JsonValue::Object(tagged) = to_json(provider)? {
+                for (type_name, fields) in tagged {
+                    let mut entry = Table::new();
+                    entry.insert(TYPE_KEY, Item::Value(type_name.into()));
+                    entry.extend(table(fields));
+                    providers.insert(name, Item::Table(entry));
+                }
+            }
+        }
+        root.insert("providers", Item::Table(providers));
+
+        let mut locations = Table::new();
+        locations.set_implicit(true);
+        for (name, location) in &self.locations {
+            locations.insert(name, Item::Table(table(to_json(location)?)));
+        }
+        root.insert("locations", Item::Table(locations));
+        Ok(root)
+    }
+}
+
+/// Text of the file and the document parsed from it, both empty if there is no file yet.
+fn read(path: &Path) -> Result<(String, DocumentMut), WeatherError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(error.into()),
+    };
+    let document = text
+        .parse()
+        .map_err(|error| corrupt_file_error(path, &error))?;
+    Ok((text, document))
+}
+
+/// JSON value of the data. Taken from the text so `f32` values keep their short form, e.g. 49.8419
+/// rather than 49.84189987182617 they have as `f64`.
+fn to_json<T: Serialize + ?Sized>(data: &T) -> Result<JsonValue, WeatherError> {
+    Ok(serde_json::from_str(&serde_json::to_string(data)?)?)
+}
+
+/// Table of the object's fields, nested objects become inline tables. Anything else gives an empty table.
+fn table(object: JsonValue) -> Table {
+    let mut table = Table::new();
+    if let JsonValue::Object(fields) = object {
+        for (key, value) in fields {
+            if let Some(value) = json_to_toml(value) {
+                table.insert(&key, Item::Value(value));
+            }
+        }
+    }
+    table
+}
+
+/// TOML has no null, `None` for it.
+fn json_to_toml(value: JsonValue) -> Option<Value> {
+    match value {
+        JsonValue::Null => None,
+        JsonValue::Bool(value) => Some(value.into()),
+        JsonValue::Number(number) => number
+            .as_i64()
+            .map(Value::from)
+            .or_else(|| number.as_f64().map(Value::from)),
+        JsonValue::String(value) => Some(value.into()),
+        JsonValue::Array(values) => Some(Value::Array(
+            values.into_iter().filter_map(json_to_toml).collect(),
+        )),
+        JsonValue::Object(fields) => Some(Value::InlineTable(
+            fields
+                .into_iter()
+                .filter_map(|(key, value)| Some((key, json_to_toml(value)?)))
+                .collect::<InlineTable>(),
+        )),
+    }
+}
+
+fn item_to_json(item: &Item) -> JsonValue {
+    match item {
+        Item::None => JsonValue::Null,
+        Item::Value(value) => toml_to_json(value),
+        Item::Table(table) => JsonValue::Object(
+            table
+                .iter()
+                .map(|(key, item)| (key.to_owned(), item_to_json(item)))
+                .collect(),
+        ),
+        Item::ArrayOfTables(tables) => JsonValue::Array(
+            tables
+                .iter()
+                .map(|table| item_to_json(&Item::Table(table.clone())))
+                .collect(),
+        ),
+    }
+}
+
+fn toml_to_json(value: &Value) -> JsonValue {
+    match value {
+        Value::String(value) => value.value().clone().into(),
+        Value::Integer(value) => (*value.value()).into(),
+        Value::Float(value) => {
+            Number::from_f64(*value.value()).map_or(JsonValue::Null, JsonValue::Number)
+        }
+        Value::Boolean(value) => (*value.value()).into(),
+        Value::Datetime(value) => value.value().to_string().into(),
+        Value::Array(values) => JsonValue::Array(values.iter().map(toml_to_json).collect()),
+        Value::InlineTable(table) => JsonValue::Object(
+            table
+                .iter()
+                .map(|(key, value)| (key.to_owned(), toml_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// File layout turned into the one of [`Content`]: `providers` become `configs` tagged by type.
+fn from_layout(mut content: JsonValue) -> JsonValue {
+    if let JsonValue::Object(root) = &mut content {
+        if let Some(JsonValue::Object(providers)) = root.remove("providers") {
+            let configs: Map<String, JsonValue> = providers
+                .into_iter()
+                .map(|(name, mut entry)| {
+                    let type_name = entry
+                        .as_object_mut()
+                        .and_then(|fields| fields.remove(TYPE_KEY))
+                        .and_then(|type_name| type_name.as_str().map(str::to_owned));
+                    match type_name {
+                        Some(type_name) => (
+                            name,
+                            JsonValue::Object(Map::from_iter([(type_name, entry)])),
+                        ),
+                        // Left for deserialization to tell the type is missing.
+                        None => (name, entry),
+                    }
+                })
+                .collect();
+            root.insert("configs".to_owned(), JsonValue::Object(configs));
+        }
+    }
+    content
+}
+
+/// Updates the existing item to the new one keeping comments and formatting of what is unchanged.
+fn merge(existing: &mut Item, new: Item) {
+    match (existing, new) {
+        (Item::Table(existing), Item::Table(new)) => merge_table(existing, new),
+        (Item::Value(existing), Item::Value(new)) => {
+            if toml_to_json(existing) != toml_to_json(&new) {
+                let decor = existing.decor().clone();
+                *existing = new;
+                *existing.decor_mut() = decor;
+            }
+        }
+        (existing, new) => *existing = new,
+    }
+}
+
+/// Applies to the current table the items that differ between the base and the new one, down to
+/// `depth` levels of tables, so items changed by others are kept.
+fn merge_changes(base: &Table, mut new: Table, current: &mut Table, depth: usize) {
+    let keys: BTreeSet<String> = base
+        .iter()
+        .chain(new.iter())
+        .map(|(key, _)| key.to_owned())
+        .collect();
+    for key in keys {
+        let old = base.get(&key);
+        if old.map(item_to_json) == new.get(&key).map(item_to_json) {
+            continue;
+        }
+        match (old, new.remove(&key), current.get_mut(&key)) {
+            (Some(Item::Table(old)), Some(Item::Table(new)), Some(Item::Table(current)))
+                if depth > 1 =>
+            {
+                merge_changes(old, new, current, depth - 1)
+            }
+            (_, Some(new), Some(existing)) => merge(existing, new),
+            (_, Some(new), None) => {
+                current.insert(&key, new);
+            }
+            (_, None, _) => {
+                current.remove(&key);
+            }
+        }
+    }
+}
+
+fn merge_table(existing: &mut Table, new: Table) {
+    let stale: Vec<String> = existing
+        .iter()
+        .map(|(key, _)| key.to_owned())
+        .filter(|key| !new.contains_key(key))
+        .collect();
+    for key in stale {
+        existing.remove(&key);
+    }
+    for (key, item) in new {
+        match existing.get_mut(&key) {
+            Some(existing) => merge(existing, item),
+            None => {
+                existing.insert(&key, item);
+            }
+        }
+    }
+}
+
+impl Storage for TomlStorage {
+    fn get_all(&self) -> Vec<&dyn WeatherProvider> {
+        self.configs.values().map(|p| p.as_ref()).collect()
+    }
+
+    fn add(&mut self, provider: Box<dyn WeatherProvider>) -> Result<(), WeatherError> {
+        self.configs.insert(provider.get_name(), provider);
+        self.save()
+    }
+
+    fn get(&mut self, key: &str) -> Option<&mut Box<dyn WeatherProvider>> {
+        self.configs.get_mut(key)
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), WeatherError> {
+        self.configs.remove(key);
+        if self.default.as_deref() == Some(key) {
+            self.default = None
+        }
+        self.save()
+    }
+
+    fn set_default_entry(&mut self, key: &str) -> Result<(), WeatherError> {
+        self.default = Some(
+            self.get(key)
+                .map(|provider| provider.get_name())
+                .ok_or(WeatherError::NoSuchProviderError)?,
+        );
+        self.save()
+    }
+
+    fn get_default_entry(&mut self) -> Option<&mut Box<dyn WeatherProvider>> {
+        self.default.clone().and_then(|name| self.get(&name))
+    }
+
+    fn get_settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    fn set_settings(&mut self, settings: Settings) -> Result<(), WeatherError> {
+        self.settings = settings;
+        self.save()
+    }
+
+    fn get_locations(&self) -> Vec<(&str, &Location)> {
+        self.locations
+            .iter()
+            .map(|(name, location)| (name.as_str(), location))
+            .collect()
+    }
+
+    fn get_location(&self, name: &str) -> Option<&Location> {
+        self.locations.get(name)
+    }
+
+    fn add_location(&mut self, name: &str, location: Location) -> Result<(), WeatherError> {
+        self.locations.insert(name.to_owned(), location);
+        self.save()
+    }
+
+    fn delete_location(&mut self, name: &str) -> Result<(), WeatherError> {
+        self.locations
+            .remove(name)
+            .ok_or_else(|| WeatherError::NoSuchLocationError(name.to_owned()))?;
+        self.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::Mutex};
+
+    use crate::{
+        api::geocoder_api::Location,
+        error::WeatherError,
+        providers::openweather_api::OpenWeatherProvider,
+        storage::storage_api::{Settings, Storage},
+        units::UnitSystem,
+    };
+
+    use super::TomlStorage;
+
+    type TestResult<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+    // Need to sync test threads sharing the file
+    static M: Mutex<()> = Mutex::new(());
+
+    static STORAGE_TOML_FILE: &str = "test_toml_storage.toml";
+
+    #[test]
+    fn providers_and_settings_ok() -> TestResult {
+        let _lock = M.lock()?;
+        let _ = fs::remove_file(STORAGE_TOML_FILE);
+
+        let mut storage = TomlStorage::new(STORAGE_TOML_FILE).unwrap();
+        storage
+            .add(Box::new(OpenWeatherProvider::new(
+                "OpenWeather",
+                Some("key"),
+            )))
+            .unwrap();
+        storage.set_default_entry("OpenWeather").unwrap();
+        storage
+            .set_settings(Settings {
+                units: UnitSystem::Imperial,
+                fallback: vec!["Open-Meteo".to_owned()],
+                ..Default::default()
+            })
+            .unwrap();
+        let office = Location {
+            name: "Lviv".to_owned(),
+            lat: 49.8419,
+            lon: 24.0315,
+            provider_ids: [("accuweather".to_owned(), "324561".to_owned())].into(),
+            ..Default::default()
+        };
+        storage.add_location("office", office.clone()).unwrap();
+        drop(storage);
+
+        let saved = fs::read_to_string(STORAGE_TOML_FILE)?;
+        assert!(saved.contains("[providers.OpenWeather]\ntype = \"OpenWeatherProvider\"\n"));
+        assert!(saved.contains("default = \"OpenWeather\""));
+        assert!(saved.contains("lat = 49.8419\n"));
+
+        let mut storage = TomlStorage::new(STORAGE_TOML_FILE).unwrap();
+        assert_eq!(
+            Some("key".to_owned()),
+            storage.get_default_entry().unwrap().get_api_key()
+        );
+        assert_eq!(UnitSystem::Imperial, storage.get_settings().units);
+        assert_eq!(Some(&office), storage.get_location("office"));
+
+        storage.delete("OpenWeather").unwrap();
+        assert!(storage.get_default_entry().is_none());
+        assert_eq!(
+            Err(WeatherError::NoSuchLocationError("home".to_owned())),
+            storage.delete_location("home")
+        );
+        fs::remove_file(STORAGE_TOML_FILE)?;
+        Ok(())
+    }
+
+    #[test]
+    fn comments_kept_on_save() -> TestResult {
+        let _lock = M.lock()?;
+
+        let hand_written = r#"# Shared by the team
+version = 1
+default = "OpenWeather"
+
+[settings]
+units   = "metric"  # the rest of us are metric
+jobs = 8
+
+# Key comes from WEATHER_OPENWEATHER_API_KEY
+[providers.OpenWeather]
+type = "OpenWeatherProvider"
+provider_name = "OpenWeather"
+base_url = "https://api.openweathermap.org"
+"#;
+        fs::write(STORAGE_TOML_FILE, hand_written)?;
+        let mut storage = TomlStorage::new(STORAGE_TOML_FILE).unwrap();
+        let settings = Settings {
+            units: UnitSystem::Imperial,
+            ..storage.get_settings().clone()
+        };
+        storage.set_settings(settings).unwrap();
+        drop(storage);
+
+        assert_eq!(
+            hand_written.replace("\"metric\"", "\"imperial\""),
+            fs::read_to_string(STORAGE_TOML_FILE)?
+        );
+        fs::remove_file(STORAGE_TOML_FILE)?;
+        Ok(())
+    }
+
+    #[test]
+    fn changes_of_other_processes_kept() -> TestResult {
+        let _lock = M.lock()?;
+        let _ = fs::remove_file(STORAGE_TOML_FILE);
+
+        let mut first = TomlStorage::new(STORAGE_TOML_FILE)?;
+        let mut second = TomlStorage::new(STORAGE_TOML_FILE)?;
+        first.add(Box::new(OpenWeatherProvider::new(
+            "First OpenWeather",
+            None,
+        )))?;
+        first.set_settings(Settings {
+            units: UnitSystem::Imperial,
+            ..Default::default()
+        })?;
+        // Settings read before the first one changed them.
+        second.set_settings(Settings {
+            jobs: Some(2),
+            ..second.get_settings().clone()
+        })?;
+        second.add_location("home", Location::default())?;
+        assert!(second.get("First OpenWeather").is_some());
+
+        let storage = TomlStorage::new(STORAGE_TOML_FILE)?;
+        assert_eq!(UnitSystem::Imperial, storage.get_settings().units);
+        assert_eq!(Some(2), storage.get_settings().jobs);
+        assert!(storage.get_location("home").is_some());
+        fs::remove_file(STORAGE_TOML_FILE)?;
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_file_error_expected() -> TestResult {
+        let _lock = M.lock()?;
+
+        fs::write(STORAGE_TOML_FILE, "[providers.OpenWeather\n")?;
+        assert!(matches!(
+            TomlStorage::new(STORAGE_TOML_FILE),
+            Err(WeatherError::CorruptConfigFileError(_))
+        ));
+        fs::write(
+            STORAGE_TOML_FILE,
+            "version = 1\n[providers.Unknown]\ntype = \"NoSuchProvider\"\n",
+        )?;
+        assert!(matches!(
+            TomlStorage::new(STORAGE_TOML_FILE),
+            Err(WeatherError::CorruptConfigFileError(_))
+        ));
+        fs::remove_file(STORAGE_TOML_FILE)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Unit system values of [`crate::api::provider_api::WeatherReport`] are expressed in.
+/// Providers always answer in [`UnitSystem::Metric`], conversion happens here only
+/// so values stay consistent whichever provider answered.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitSystem {
+    /// °C, m/s, hPa, mm.
+    #[default]
+    Metric,
+
+    /// °F, mph, inHg, in.
+    Imperial,
+
+    /// K, m/s, Pa, mm.
+    Si,
+}
+
+impl UnitSystem {
+    pub fn temperature_unit(&self) -> &'static str {
+        match self {
+            Self::Metric => "C",
+            Self::Imperial => "F",
+            Self::Si => "K",
+        }
+    }
+
+    pub fn speed_unit(&self) -> &'static str {
+        match self {
+            Self::Metric | Self::Si => "m/s",
+            Self::Imperial => "mph",
+        }
+    }
+
+    pub fn pressure_unit(&self) -> &'static str {
+        match self {
+            Self::Metric => "hPa",
+            Self::Imperial => "inHg",
+            Self::Si => "Pa",
+        }
+    }
+
+    pub fn precipitation_unit(&self) -> &'static str {
+        match self {
+            Self::Metric | Self::Si => "mm",
+            Self::Imperial => "in",
+        }
+    }
+
+    /// Converts temperature from `self` to `to`.
+    pub fn convert_temperature(&self, value: f32, to: UnitSystem) -> f32 {
+        let celsius = match self {
+            Self::Metric => value,
+            Self::Imperial => (value - 32.0) * 5.0 / 9.0,
+            Self::Si => value - 273.15,
+        };
+        round(match to {
+            Self::Metric => celsius,
+            Self::Imperial => celsius * 9.0 / 5.0 + 32.0,
+            Self::Si => celsius + 273.15,
+        })
+    }
+
+    /// Converts speed from `self` to `to`.
+    pub fn convert_speed(&self, value: f32, to: UnitSystem) -> f32 {
+        let meters_per_second = match self {
+            Self::Metric | Self::Si => value,
+            Self::Imperial => value / MPH_PER_MS,
+        };
+        round(match to {
+            Self::Metric | Self::Si => meters_per_second,
+            Self::Imperial => meters_per_second * MPH_PER_MS,
+        })
+    }
+
+    /// Converts pressure from `self` to `to`.
+    pub fn convert_pressure(&self, value: f32, to: UnitSystem) -> f32 {
+        let hectopascals = match self {
+            Self::Metric => value,
+            Self::Imperial => value / INHG_PER_HPA,
+            Self::Si => value / 100.0,
+        };
+        round(match to {
+            Self::Metric => hectopascals,
+            Self::Imperial => hectopascals * INHG_PER_HPA,
+            Self::Si => hectopascals * 100.0,
+        })
+    }
+
+    /// Converts precipitation amount from `self` to `to`.
+    pub fn convert_precipitation(&self, value: f32, to: UnitSystem) -> f32 {
+        let millimeters = match self {
+            Self::Metric | Self::Si => value,
+            Self::Imperial => value * MM_PER_INCH,
+        };
+        round(match to {
+            Self::Metric | Self::Si => millimeters,
+            Self::Imperial => millimeters / MM_PER_INCH,
+        })
+    }
+}
+
+impl fmt::Display for UnitSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Metric => write!(f, "metric"),
+            Self::Imperial => write!(f, "imperial"),
+            Self::Si => write!(f, "si"),
+        }
+    }
+}
+
+const MPH_PER_MS: f32 = 2.236_936;
+const INHG_PER_HPA: f32 = 0.029_53;
+const MM_PER_INCH: f32 = 25.4;
+
+/// Keeps two decimals to hide floating point noise of conversions.
+fn round(value: f32) -> f32 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnitSystem;
+
+    #[test]
+    fn temperature_conversion_ok() {
+        assert_eq!(
+            50.0,
+            UnitSystem::Metric.convert_temperature(10.0, UnitSystem::Imperial)
+        );
+        assert_eq!(
+            283.15,
+            UnitSystem::Metric.convert_temperature(10.0, UnitSystem::Si)
+        );
+        assert_eq!(
+            10.0,
+            UnitSystem::Imperial.convert_temperature(50.0, UnitSystem::Metric)
+        );
+        assert_eq!(
+            10.0,
+            UnitSystem::Si.convert_temperature(283.15, UnitSystem::Metric)
+        );
+    }
+
+    #[test]
+    fn speed_conversion_ok() {
+        assert_eq!(
+            22.37,
+            UnitSystem::Metric.convert_speed(10.0, UnitSystem::Imperial)
+        );
+        assert_eq!(10.0, UnitSystem::Metric.convert_speed(10.0, UnitSystem::Si));
+        assert_eq!(
+            10.0,
+            UnitSystem::Imperial.convert_speed(22.37, UnitSystem::Si)
+        );
+    }
+
+    #[test]
+    fn pressure_and_precipitation_conversion_ok() {
+        assert_eq!(
+            29.88,
+            UnitSystem::Metric.convert_pressure(1012.0, UnitSystem::Imperial)
+        );
+        assert_eq!(
+            101200.0,
+            UnitSystem::Metric.convert_pressure(1012.0, UnitSystem::Si)
+        );
+        assert_eq!(
+            1.0,
+            UnitSystem::Metric.convert_precipitation(25.4, UnitSystem::Imperial)
+        );
+    }
+}